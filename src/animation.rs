@@ -0,0 +1,156 @@
+use std::ops::{Add, Mul, Sub};
+
+use crate::{transform::Quaternion, vec3::Vector3};
+
+// How values are interpolated between keyframes.
+#[derive(Clone, Copy)]
+pub enum Interpolation {
+    // Straight lines between keys, with sudden changes of speed at each key.
+    Linear,
+    // Catmull-Rom spline through the keys, with smooth motion across them.
+    CatmullRom,
+}
+
+// Value that changes over time, given at keyframes and interpolated in between. Before the first
+// key and after the last the value is held.
+#[derive(Clone)]
+pub struct Keyframes<T> {
+    keys: Vec<(f64, T)>, // Frame and value of each key, in frame order
+    interpolation: Interpolation,
+}
+
+impl<T: Copy> Keyframes<T> {
+    pub fn new(mut keys: Vec<(f64, T)>, interpolation: Interpolation) -> Keyframes<T> {
+        assert!(!keys.is_empty(), "keyframes need at least one key");
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Keyframes {
+            keys,
+            interpolation,
+        }
+    }
+
+    // Value that stays the same at every frame.
+    pub fn constant(value: T) -> Keyframes<T> {
+        Keyframes::new(vec![(0.0, value)], Interpolation::Linear)
+    }
+
+    // Index of the key that starts the segment containing the frame, and how far through the
+    // segment the frame is. Frames outside the keys are clamped to the ends.
+    fn segment(&self, frame: f64) -> (usize, f64) {
+        let last = self.keys.len() - 1;
+        if last == 0 || frame <= self.keys[0].0 {
+            return (0, 0.0);
+        }
+        if frame >= self.keys[last].0 {
+            return (last - 1, 1.0);
+        }
+
+        let i = self.keys.partition_point(|key| key.0 <= frame) - 1;
+        let (f1, f2) = (self.keys[i].0, self.keys[i + 1].0);
+        (i, (frame - f1) / (f2 - f1))
+    }
+}
+
+impl<T> Keyframes<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    pub fn at(&self, frame: f64) -> T {
+        if self.keys.len() == 1 {
+            return self.keys[0].1;
+        }
+
+        let last = self.keys.len() - 1;
+        let (i, t) = self.segment(frame);
+        let p1 = self.keys[i].1;
+        let p2 = self.keys[i + 1].1;
+
+        match self.interpolation {
+            Interpolation::Linear => p1 + (p2 - p1) * t,
+            Interpolation::CatmullRom => {
+                // The end keys are repeated to give the spline its outer control points.
+                let p0 = self.keys[i.saturating_sub(1)].1;
+                let p3 = self.keys[(i + 2).min(last)].1;
+                catmull_rom(p0, p1, p2, p3, t)
+            }
+        }
+    }
+}
+
+impl Keyframes<Quaternion> {
+    // Rotation at the frame. Rotations are always interpolated along the shortest arc between
+    // neighbouring keys, whatever the interpolation mode.
+    pub fn slerp_at(&self, frame: f64) -> Quaternion {
+        if self.keys.len() == 1 {
+            return self.keys[0].1;
+        }
+
+        let (i, t) = self.segment(frame);
+        Quaternion::slerp(self.keys[i].1, self.keys[i + 1].1, t)
+    }
+}
+
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, t: f64) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let a = p1 * 2.0;
+    let b = (p2 - p0) * t;
+    let c = (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2;
+    let d = (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3;
+    (a + b + c + d) * 0.5
+}
+
+// Camera view at a single frame.
+#[derive(Clone, Copy)]
+pub struct CameraKeyframe {
+    pub frame: f64,
+    pub look_from: Vector3,
+    pub look_at: Vector3,
+    pub v_fov: f64,
+    pub focus_dist: f64,
+}
+
+// Path of a camera through a shot, such as a turntable orbit or a fly-through.
+pub struct CameraPath {
+    look_from: Keyframes<Vector3>,
+    look_at: Keyframes<Vector3>,
+    v_fov: Keyframes<f64>,
+    focus_dist: Keyframes<f64>,
+}
+
+impl CameraPath {
+    pub fn new(keyframes: &[CameraKeyframe], interpolation: Interpolation) -> CameraPath {
+        CameraPath {
+            look_from: track(keyframes, interpolation, |k| k.look_from),
+            look_at: track(keyframes, interpolation, |k| k.look_at),
+            v_fov: track(keyframes, interpolation, |k| k.v_fov),
+            focus_dist: track(keyframes, interpolation, |k| k.focus_dist),
+        }
+    }
+
+    pub fn at(&self, frame: f64) -> CameraKeyframe {
+        CameraKeyframe {
+            frame,
+            look_from: self.look_from.at(frame),
+            look_at: self.look_at.at(frame),
+            v_fov: self.v_fov.at(frame),
+            focus_dist: self.focus_dist.at(frame),
+        }
+    }
+}
+
+// Keyframes of one camera setting.
+fn track<T>(
+    keyframes: &[CameraKeyframe],
+    interpolation: Interpolation,
+    value: impl Fn(&CameraKeyframe) -> T,
+) -> Keyframes<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    let keys = keyframes.iter().map(|k| (k.frame, value(k))).collect();
+    Keyframes::new(keys, interpolation)
+}
@@ -0,0 +1,87 @@
+use std::f64::consts::PI;
+
+use crate::{
+    distribution::Distribution2D, image::Image, sampler::sample_unit_disk,
+    utils::degrees_to_radians,
+};
+
+// Shape of the lens opening, which sets the shape of out-of-focus highlights.
+pub enum Aperture {
+    // Round opening, giving circular bokeh.
+    Circle,
+    // Regular polygon formed by straight diaphragm blades, rotated by `rotation` degrees.
+    Polygon { blades: u32, rotation: f64 },
+    // Arbitrary opening given by the brightness of an image, for shaped or apodized bokeh.
+    Image(ApertureImage),
+}
+
+impl Aperture {
+    pub fn from_image(image: &Image) -> Aperture {
+        Aperture::Image(ApertureImage::new(image))
+    }
+
+    // Maps a uniform 2D sample to a point on the aperture, scaled to fit in the unit disk.
+    pub fn sample(&self, u: (f64, f64)) -> (f64, f64) {
+        match self {
+            Aperture::Circle => {
+                let p = sample_unit_disk(u);
+                (p[0], p[1])
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // The polygon is a fan of equal triangles around its center. Pick one, then a
+                // uniform point inside it, reusing what is left of the first sample after the
+                // pick so that both stay uniform.
+                let blades = (*blades).max(3);
+                let sector = 2.0 * PI / blades as f64;
+                let picked = u.0 * blades as f64;
+                let k = picked.floor().min(blades as f64 - 1.0);
+                let a0 = degrees_to_radians(*rotation) + k * sector;
+                let a1 = a0 + sector;
+
+                let mut s = (picked - k).min(1.0);
+                let mut t = u.1;
+                if s + t > 1.0 {
+                    s = 1.0 - s;
+                    t = 1.0 - t;
+                }
+                (s * a0.cos() + t * a1.cos(), s * a0.sin() + t * a1.sin())
+            }
+            Aperture::Image(image) => image.sample(u),
+        }
+    }
+}
+
+// Aperture image prepared for sampling points in proportion to pixel brightness.
+pub struct ApertureImage {
+    distribution: Distribution2D,
+    scale: (f64, f64),
+}
+
+impl ApertureImage {
+    pub fn new(image: &Image) -> ApertureImage {
+        let (width, height) = (image.width(), image.height());
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                func.push(image.luminance(x, y));
+            }
+        }
+
+        // The longer side of the image spans the diameter of the unit disk.
+        let longest = width.max(height) as f64;
+
+        ApertureImage {
+            distribution: Distribution2D::new(&func, width, height),
+            scale: (width as f64 / longest, height as f64 / longest),
+        }
+    }
+
+    fn sample(&self, u: (f64, f64)) -> (f64, f64) {
+        let ((x, y), _) = self.distribution.sample_continuous(u);
+        // Image rows run downwards, while the lens v axis points up.
+        (
+            self.scale.0 * (2.0 * x - 1.0),
+            self.scale.1 * (1.0 - 2.0 * y),
+        )
+    }
+}
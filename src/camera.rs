@@ -1,29 +1,227 @@
-use rand::Rng;
+use std::{
+    f64::consts::PI,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::RangeInclusive,
+    sync::Arc,
+};
 
 use crate::{
+    animation::CameraPath,
+    aperture::Aperture,
     color::{ray_color, write_color},
+    filter::{Filter, FilterKind},
     hittable::HittableList,
+    lens::LensSystem,
     ray::Ray,
+    sampler::{Sampler, SamplerKind},
+    spectrum::Wavelengths,
     utils::degrees_to_radians,
     vec3::Vector3,
 };
 
+// How camera rays leave the viewport.
+#[derive(Clone)]
+pub enum Projection {
+    // Rays fan out from the camera center through the viewport, covering the vertical field of
+    // view.
+    Perspective,
+    // Rays run parallel to the view direction from a viewport of the given height in world units,
+    // so parallel lines in the scene stay parallel in the image.
+    Orthographic { viewport_height: f64 },
+    // Latitude-longitude panorama covering the full sphere around the camera, with the view
+    // direction at the image center and `vup` at the top edge.
+    Equirectangular,
+    // Circular fisheye inscribed in the image, covering `fov` degrees across its diameter.
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+    // Six 90° faces laid out in a 3x2 grid: right, left and up on the top row, then down, front
+    // and back. The up and down faces have their top edges towards the back and front faces.
+    Cubemap,
+    // Rays traced from the film through a lens system, which is focused at the focus distance.
+    // The field of view follows from the lens and a full-frame sensor height.
+    Realistic(Arc<LensSystem>),
+}
+
+// How the distance from the fisheye image center maps to the angle from the view direction.
+#[derive(Clone, Copy)]
+pub enum FisheyeMapping {
+    // Distance proportional to the angle, r = f·θ.
+    Equidistant,
+    // Equal solid angle per image area, r = 2f·sin(θ/2).
+    Equisolid,
+}
+
+// How the two eye views of a stereo render are packed into the output image.
+#[derive(Clone, Copy)]
+pub enum StereoLayout {
+    // Left eye on the left half, right eye on the right half.
+    SideBySide,
+    // Left eye on the top half, right eye on the bottom half.
+    TopBottom,
+}
+
+// Stereo pair settings. Each eye renders a full `image_width` by `image_height` view.
+#[derive(Clone, Copy)]
+pub struct Stereo {
+    pub interocular_distance: f64, // Distance between the eyes in world units
+    pub convergence_distance: f64, // Distance at which both eyes see the same point
+    pub layout: StereoLayout,
+}
+
+// Brown–Conrady lens distortion, with radial coefficients k1 to k3 and tangential coefficients
+// p1 and p2. Coordinates are normalized by the focal length in pixels, with y pointing down, as
+// in common camera calibration tools.
+#[derive(Clone, Copy)]
+pub struct Distortion {
+    pub k1: f64,
+    pub k2: f64,
+    pub k3: f64,
+    pub p1: f64,
+    pub p2: f64,
+}
+
+impl Distortion {
+    // Maps ideal image coordinates to where the lens puts them.
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+        (
+            x * radial + 2.0 * self.p1 * x * y + self.p2 * (r2 + 2.0 * x * x),
+            y * radial + self.p1 * (r2 + 2.0 * y * y) + 2.0 * self.p2 * x * y,
+        )
+    }
+
+    // Maps distorted image coordinates back to ideal ones, inverting `apply` by fixed-point
+    // iteration.
+    pub fn remove(&self, xd: f64, yd: f64) -> (f64, f64) {
+        let (mut x, mut y) = (xd, yd);
+        for _ in 0..20 {
+            let r2 = x * x + y * y;
+            let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+            let dx = 2.0 * self.p1 * x * y + self.p2 * (r2 + 2.0 * x * x);
+            let dy = self.p1 * (r2 + 2.0 * y * y) + 2.0 * self.p2 * x * y;
+            x = (xd - dx) / radial;
+            y = (yd - dy) / radial;
+        }
+        (x, y)
+    }
+}
+
+// Height of the full-frame sensor the field of view is measured against, in millimeters.
+const SENSOR_HEIGHT: f64 = 24.0;
+
+// Photographic exposure settings. Radiance is taken to be in physical units and world units in
+// meters, so renders can be matched against photographs taken with the same settings.
+#[derive(Clone, Copy)]
+pub struct Exposure {
+    pub f_number: f64, // Aperture as a fraction of the focal length, e.g. 2.8 for f/2.8
+    pub shutter_time: f64, // Shutter speed in seconds
+    pub iso: f64,      // Sensor sensitivity
+    pub compensation: f64, // Exposure compensation in stops
+}
+
+impl Exposure {
+    // Exposure value at ISO 100 for these settings, after compensation.
+    pub fn ev100(&self) -> f64 {
+        (self.f_number * self.f_number / self.shutter_time * 100.0 / self.iso).log2()
+            - self.compensation
+    }
+
+    // Scale from scene radiance to pixel values, using the saturation-based sensitivity of ISO
+    // 12232 so that the brightest unclipped value maps to 1.
+    pub fn scale(&self) -> f64 {
+        1.0 / (1.2 * 2.0_f64.powf(self.ev100()))
+    }
+}
+
+// Adaptive sampling settings, which replace the fixed samples per pixel. Every pixel takes at
+// least `min_samples`, then keeps sampling until its estimated noise falls below `threshold` or
+// it reaches `max_samples`, so that smooth areas such as the sky finish early.
+//
+// Samplers are set up for `max_samples`. Stratified sampling lays its grid out for that many
+// samples, so pixels that stop early only fill part of it and lose most of the benefit; the
+// low-discrepancy samplers are well distributed at any prefix of their samples.
+#[derive(Clone)]
+pub struct AdaptiveSampling {
+    pub min_samples: i32,
+    pub max_samples: i32,
+    // Largest standard error of a pixel's luminance left, relative to the square root of the
+    // luminance as a rough measure of how visible the noise is after gamma, e.g. 0.01.
+    pub threshold: f64,
+    // PPM file to write with the samples taken in each pixel, from black for `min_samples` to
+    // white for `max_samples`. Each frame of a sequence overwrites it.
+    pub heatmap: Option<String>,
+}
+
+// Running mean and variance of a pixel's samples, using Welford's algorithm.
+#[derive(Default)]
+struct PixelStatistics {
+    count: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelStatistics {
+    fn push(&mut self, value: f64) {
+        self.count += 1.0;
+        let delta = value - self.mean;
+        self.mean += delta / self.count;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    // Standard error of the mean relative to the square root of the mean.
+    fn relative_error(&self) -> f64 {
+        if self.count < 2.0 {
+            return f64::INFINITY;
+        }
+        let standard_error = (self.m2 / (self.count - 1.0) / self.count).sqrt();
+        standard_error / self.mean.max(1e-4).sqrt()
+    }
+}
+
 pub struct Camera {
-    image_width: i32,         // Rendered image width in pixel count
-    image_height: i32,        // Rendered image height in pixel count
-    center: Vector3,          //
-    pixel00_loc: Vector3,     //
-    pixel_delta_u: Vector3,   //
-    pixel_delta_v: Vector3,   //
-    samples_per_pixel: i32,   // Count of random samples for each pixel
-    pixel_samples_scale: f64, //
-    max_depth: i32,           // Maximum number of ray bounces into scene
-    defocus_angle: f64,       // Variation angle of rays through each pixel
-    defocus_disk_u: Vector3,  // Defocus disk horizontal radius
-    defocus_disk_v: Vector3,  // Defocus disk vertical radius
+    image_width: i32,                   // Rendered image width in pixel count
+    image_height: i32,                  // Rendered image height in pixel count
+    v_fov: f64,                         // Vertical view angle (field of view)
+    look_from: Vector3,                 // Point camera is looking from
+    look_at: Vector3,                   // Point camera is looking at
+    vup: Vector3,                       // Camera-relative "up" direction
+    focus_dist: f64,                    // Distance from look_from to the plane of perfect focus
+    projection: Projection,             // How rays leave the camera
+    center: Vector3,                    //
+    u: Vector3,                         // Camera frame basis vectors
+    v: Vector3,                         //
+    w: Vector3,                         //
+    pixel00_loc: Vector3,               //
+    pixel_delta_u: Vector3,             //
+    pixel_delta_v: Vector3,             //
+    samples_per_pixel: i32,             // Count of random samples for each pixel
+    max_depth: i32,                     // Maximum number of ray bounces into scene
+    defocus_angle: f64,                 // Variation angle of rays through each pixel
+    thin_lens_defocus_angle: f64,       // Defocus angle given without physical exposure
+    defocus_disk_u: Vector3,            // Defocus disk horizontal radius
+    defocus_disk_v: Vector3,            // Defocus disk vertical radius
+    spectral: bool,                     // Trace sampled wavelengths instead of RGB
+    stereo: Option<Stereo>,             // Render a left/right eye pair instead of a single view
+    aperture: Aperture,                 // Shape of the defocus disk
+    cat_eye: f64,                       // Offset of the clipping pupil towards the image edges
+    exposure: Option<Exposure>,         // Physical exposure settings, or none for unscaled radiance
+    lens: Option<LensSystem>,           // Lens system of a realistic projection, focused
+    distortion: Option<Distortion>,     // Lens distortion of viewport projections
+    shift: (f64, f64),                  // Viewport offset as a fraction of its width and height
+    tilt: f64,                          // Rotation of the focus plane about the horizontal axis
+    swing: f64,                         // Rotation of the focus plane about the vertical axis
+    focus_normal: Vector3,              // Normal of the (possibly tilted) plane of focus
+    frame: f64,                         // Frame being rendered
+    shutter: (f64, f64),                // Shutter open and close times relative to the frame
+    filter: Filter,                     // Pixel reconstruction filter
+    sampler: SamplerKind,               // How sample values are placed within each pixel
+    seed: u64,                          // Seed of all random choices made while rendering
+    adaptive: Option<AdaptiveSampling>, // Noise-driven sample counts instead of a fixed one
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         image_width: i32,
         aspect_ratio: f64,
@@ -41,101 +239,699 @@ impl Camera {
             image_height = 1;
         }
 
-        let pixel_samples_scale = 1.0 / (samples_per_pixel as f64);
-
-        let center = look_from;
+        let zero = Vector3::new(0.0, 0.0, 0.0);
+        let mut camera = Camera {
+            image_width,
+            image_height,
+            v_fov,
+            look_from,
+            look_at,
+            vup,
+            focus_dist,
+            projection: Projection::Perspective,
+            center: zero,
+            u: zero,
+            v: zero,
+            w: zero,
+            pixel00_loc: zero,
+            pixel_delta_u: zero,
+            pixel_delta_v: zero,
+            samples_per_pixel,
+            max_depth,
+            defocus_angle,
+            thin_lens_defocus_angle: defocus_angle,
+            defocus_disk_u: zero,
+            defocus_disk_v: zero,
+            spectral: false,
+            stereo: None,
+            aperture: Aperture::Circle,
+            cat_eye: 0.0,
+            exposure: None,
+            lens: None,
+            distortion: None,
+            shift: (0.0, 0.0),
+            tilt: 0.0,
+            swing: 0.0,
+            focus_normal: zero,
+            frame: 0.0,
+            shutter: (0.0, 0.0),
+            filter: Filter::new(FilterKind::Box, 0.5),
+            sampler: SamplerKind::Independent,
+            seed: 0,
+            adaptive: None,
+        };
+        camera.initialize();
+        camera
+    }
 
-        // Determine viewport dimensions.
-        let theta = degrees_to_radians(v_fov);
-        let h = (theta / 2.0).tan();
-        let viewport_height = 2.0 * h * focus_dist;
-        let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
+    // Recomputes the camera frame and viewport from the view parameters.
+    fn initialize(&mut self) {
+        self.center = self.look_from;
 
         // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
-        let w = (look_from - look_at).unit_vector();
-        let u = vup.cross(w).unit_vector();
-        let v = w.cross(u);
+        self.w = (self.look_from - self.look_at).unit_vector();
+        self.u = self.vup.cross(self.w).unit_vector();
+        self.v = self.w.cross(self.u);
+
+        // Determine viewport dimensions. The perspective viewport sits on the focus plane, while
+        // the orthographic one sits at the camera center and has a fixed size. Panoramic
+        // projections don't use the viewport.
+        let (viewport_height, viewport_center) = match self.projection {
+            Projection::Orthographic { viewport_height } => (viewport_height, self.center),
+            _ => {
+                let theta = degrees_to_radians(self.v_fov);
+                let h = (theta / 2.0).tan();
+                (
+                    2.0 * h * self.focus_dist,
+                    self.center - self.focus_dist * self.w,
+                )
+            }
+        };
+        let viewport_width = viewport_height * (self.image_width as f64 / self.image_height as f64);
+
+        // Shift the viewport in its own plane, as a shift lens does, to reframe without turning
+        // the camera and so keep verticals parallel.
+        let viewport_center = viewport_center
+            + self.shift.0 * viewport_width * self.u
+            + self.shift.1 * viewport_height * self.v;
 
         // Calculate the vectors across the horizontal and down the vertical viewport edges.
-        let viewport_u = viewport_width * u; // Vector across viewport horizontal edge
-        let viewport_v = viewport_height * -v; // Vector down viewport vertical edge
+        let viewport_u = viewport_width * self.u; // Vector across viewport horizontal edge
+        let viewport_v = viewport_height * -self.v; // Vector down viewport vertical edge
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel.
-        let pixel_delta_u = viewport_u / (image_width as f64);
-        let pixel_delta_v = viewport_v / (image_height as f64);
+        self.pixel_delta_u = viewport_u / (self.image_width as f64);
+        self.pixel_delta_v = viewport_v / (self.image_height as f64);
 
         // Calculate the location of the upper left pixel.
-        let viewport_upper_left = center - (focus_dist * w) - viewport_u / 2.0 - viewport_v / 2.0;
-        let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
+        let viewport_upper_left = viewport_center - viewport_u / 2.0 - viewport_v / 2.0;
+        self.pixel00_loc = viewport_upper_left + 0.5 * (self.pixel_delta_u + self.pixel_delta_v);
+
+        // With physical exposure the defocus angle follows from the f-number.
+        self.defocus_angle = match self.exposure {
+            Some(exposure) => {
+                let aperture_radius = self.focal_length() / exposure.f_number / 2.0 / 1000.0;
+                2.0 * (aperture_radius / self.focus_dist).atan().to_degrees()
+            }
+            None => self.thin_lens_defocus_angle,
+        };
 
         // Calculate the camera defocus disk basis vectors.
-        let defocus_radius = focus_dist * degrees_to_radians(defocus_angle / 2.0).tan();
-        let defocus_disk_u = u * defocus_radius;
-        let defocus_disk_v = v * defocus_radius;
+        let defocus_radius = self.focus_dist * degrees_to_radians(self.defocus_angle / 2.0).tan();
+        self.defocus_disk_u = self.u * defocus_radius;
+        self.defocus_disk_v = self.v * defocus_radius;
 
-        Camera {
-            image_width,
-            image_height,
-            center,
-            pixel00_loc,
-            pixel_delta_u,
-            pixel_delta_v,
-            samples_per_pixel,
-            pixel_samples_scale,
-            max_depth,
-            defocus_angle,
-            defocus_disk_u,
-            defocus_disk_v,
+        // Tilt and swing rotate the plane of focus away from the film plane, following the
+        // Scheimpflug principle: positive values push it further away at the bottom and right.
+        let tilted = rotate_about(self.w, self.u, -degrees_to_radians(self.tilt));
+        self.focus_normal = rotate_about(tilted, self.v, degrees_to_radians(self.swing));
+
+        // Move the lens elements of a realistic projection to focus, as a real lens does.
+        self.lens = match &self.projection {
+            Projection::Realistic(lens) => {
+                let film_height = self.film_pixel_pitch() * self.image_height as f64;
+                let film_width = self.film_pixel_pitch() * self.image_width as f64;
+                let film_diagonal = (film_width * film_width + film_height * film_height).sqrt();
+                Some(lens.focus(self.focus_dist, film_diagonal))
+            }
+            _ => None,
+        };
+    }
+
+    // Moves the camera to a new view, keeping every other setting.
+    pub fn set_view(&mut self, look_from: Vector3, look_at: Vector3, v_fov: f64, focus_dist: f64) {
+        self.look_from = look_from;
+        self.look_at = look_at;
+        self.v_fov = v_fov;
+        self.focus_dist = focus_dist;
+        self.initialize();
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        self.initialize();
+    }
+
+    pub fn set_spectral(&mut self, spectral: bool) {
+        self.spectral = spectral;
+    }
+
+    pub fn set_stereo(&mut self, stereo: Option<Stereo>) {
+        self.stereo = stereo;
+    }
+
+    pub fn set_aperture(&mut self, aperture: Aperture) {
+        self.aperture = aperture;
+    }
+
+    // Clips the aperture towards the edges of the frame, as the barrel of a real lens does, so
+    // that bokeh turns into cat's eyes and the corners darken. At a strength of 1 the aperture
+    // is fully closed in the corners of the image.
+    pub fn set_cat_eye(&mut self, strength: f64) {
+        self.cat_eye = strength;
+    }
+
+    // Sets physical exposure. The f-number also sets the defocus angle, from the aperture
+    // diameter of a lens with the focal length matching the field of view. Clearing it restores
+    // the defocus angle the camera was created with.
+    pub fn set_exposure(&mut self, exposure: Option<Exposure>) {
+        self.exposure = exposure;
+        self.initialize();
+    }
+
+    // Focal length in millimeters giving the vertical field of view on a full-frame sensor.
+    pub fn focal_length(&self) -> f64 {
+        0.5 * SENSOR_HEIGHT / (degrees_to_radians(self.v_fov) / 2.0).tan()
+    }
+
+    // Size of a pixel on the full-frame sensor, in meters.
+    fn film_pixel_pitch(&self) -> f64 {
+        SENSOR_HEIGHT / 1000.0 / self.image_height as f64
+    }
+
+    // Distorts the rendered image like a real lens, for matching footage. Only applies to the
+    // perspective and orthographic projections.
+    pub fn set_distortion(&mut self, distortion: Option<Distortion>) {
+        self.distortion = distortion;
+    }
+
+    // Shifts the viewport by fractions of its width and height, positive being right and up.
+    pub fn set_shift(&mut self, horizontal: f64, vertical: f64) {
+        self.shift = (horizontal, vertical);
+        self.initialize();
+    }
+
+    // Tilts the plane of focus by `tilt` degrees about the horizontal axis and `swing` degrees
+    // about the vertical axis, keeping it through the focus distance on the view axis.
+    pub fn set_tilt(&mut self, tilt: f64, swing: f64) {
+        self.tilt = tilt;
+        self.swing = swing;
+        self.initialize();
+    }
+
+    // Sets the frame that animated objects are shown at.
+    pub fn set_frame(&mut self, frame: f64) {
+        self.frame = frame;
+    }
+
+    // Keeps the shutter open from `open` to `close` frames around the current frame, so that
+    // objects moving in that time are motion blurred. E.g. (-0.25, 0.25) for a 180° shutter.
+    pub fn set_shutter(&mut self, open: f64, close: f64) {
+        self.shutter = (open, close);
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    pub fn set_sampler(&mut self, sampler: SamplerKind) {
+        self.sampler = sampler;
+    }
+
+    // Renders with the same seed give identical images. Changing it gives a different, equally
+    // valid sampling of the same image.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn set_adaptive(&mut self, adaptive: Option<AdaptiveSampling>) {
+        self.adaptive = adaptive;
+    }
+
+    // Scale from pixels to the normalized image coordinates used by lens distortion.
+    fn distortion_scale(&self) -> f64 {
+        let half_height = 0.5 * self.image_height as f64;
+        match self.projection {
+            Projection::Orthographic { .. } => 1.0 / half_height,
+            _ => (degrees_to_radians(self.v_fov) / 2.0).tan() / half_height,
+        }
+    }
+
+    // Direction from `lens_point` that focuses on where the ray from `origin` along `direction`
+    // crosses the plane of focus. Rays that never reach the plane are focused at infinity.
+    fn focused_direction(
+        &self,
+        origin: Vector3,
+        direction: Vector3,
+        lens_point: Vector3,
+    ) -> Vector3 {
+        let plane_point = self.center - self.focus_dist * self.w;
+        let t = (plane_point - origin).dot(self.focus_normal) / direction.dot(self.focus_normal);
+        if t.is_finite() && t > 0.0 {
+            origin + t * direction - lens_point
+        } else {
+            direction
+        }
+    }
+
+    // Dimensions of the output image, which holds both eyes in stereo mode.
+    fn output_size(&self) -> (i32, i32) {
+        match self.stereo.map(|stereo| stereo.layout) {
+            None => (self.image_width, self.image_height),
+            Some(StereoLayout::SideBySide) => (2 * self.image_width, self.image_height),
+            Some(StereoLayout::TopBottom) => (self.image_width, 2 * self.image_height),
+        }
+    }
+
+    // Maps an output pixel to the pixel within its eye view and the signed offset of that eye
+    // along the camera's horizontal axis.
+    fn eye_pixel(&self, i: i32, j: i32) -> (i32, i32, f64) {
+        let Some(stereo) = self.stereo else {
+            return (i, j, 0.0);
+        };
+
+        let half = stereo.interocular_distance / 2.0;
+        match stereo.layout {
+            StereoLayout::SideBySide if i >= self.image_width => (i - self.image_width, j, half),
+            StereoLayout::TopBottom if j >= self.image_height => (i, j - self.image_height, half),
+            _ => (i, j, -half),
+        }
+    }
+
+    fn convergence_distance(&self) -> f64 {
+        self.stereo
+            .map_or(self.focus_dist, |stereo| stereo.convergence_distance)
+    }
+
+    fn get_ray(
+        &self,
+        i: i32,
+        j: i32,
+        offset: (f64, f64),
+        eye_shift: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Ray> {
+        // Construct a camera ray originating from the defocus disk and directed at the point
+        // `offset` pixels away from the center of the pixel location i, j. Pixels outside the
+        // image area of the projection have no ray. A non-zero `eye_shift` moves the eye along
+        // the horizontal axis for stereo rendering.
+
+        // Drawn whether or not the lens uses it, so that later dimensions line up between
+        // projections.
+        let u_lens = sampler.get_2d();
+
+        let film_x = i as f64 + 0.5 + offset.0;
+        let film_y = j as f64 + 0.5 + offset.1;
+        let mut pixel_sample = self.pixel00_loc
+            + (((i as f64) + offset.0) * self.pixel_delta_u)
+            + (((j as f64) + offset.1) * self.pixel_delta_v);
+
+        // The film position is where the distorting lens puts the image, so look up the ideal
+        // position it came from.
+        if let Some(distortion) = self.distortion {
+            let scale = self.distortion_scale();
+            let cx = 0.5 * self.image_width as f64;
+            let cy = 0.5 * self.image_height as f64;
+            let (x, y) = distortion.remove((film_x - cx) * scale, (film_y - cy) * scale);
+            let dx = x / scale + cx - film_x;
+            let dy = y / scale + cy - film_y;
+            pixel_sample += dx * self.pixel_delta_u + dy * self.pixel_delta_v;
+        }
+
+        match self.projection {
+            Projection::Perspective => {
+                // Off-axis stereo: each eye aims through the point where the pixel lies on the
+                // convergence plane, so the eyes' views coincide there without toeing in.
+                let eye = self.center + eye_shift * self.u;
+                let convergence = self.convergence_distance();
+                let target =
+                    self.center + (pixel_sample - self.center) * (convergence / self.focus_dist);
+
+                let ray_origin = if self.defocus_angle <= 0.0 {
+                    eye
+                } else {
+                    self.defocus_disk_sample(eye, film_x, film_y, u_lens)?
+                };
+                let ray_direction = self.focused_direction(eye, target - eye, ray_origin);
+
+                Some(Ray::new(ray_origin, ray_direction))
+            }
+            Projection::Orthographic { .. } => {
+                // Every ray runs along the view direction, tilted in stereo so that both eyes meet
+                // on the convergence plane. With defocus, rays leave a disk around the film
+                // point and converge on the focus plane.
+                let film_point = pixel_sample + eye_shift * self.u;
+                let convergence = self.convergence_distance();
+                let direction = (pixel_sample - convergence * self.w) - film_point;
+                if self.defocus_angle <= 0.0 {
+                    return Some(Ray::new(film_point, direction));
+                }
+                let ray_origin = self.defocus_disk_sample(film_point, film_x, film_y, u_lens)?;
+                let ray_direction = self.focused_direction(film_point, direction, ray_origin);
+
+                Some(Ray::new(ray_origin, ray_direction))
+            }
+            Projection::Realistic(_) => {
+                // The lens forms an inverted image, so the film is read rotated by 180°.
+                let lens = self.lens.as_ref()?;
+                let pitch = self.film_pixel_pitch();
+                let film = (
+                    (0.5 * self.image_width as f64 - film_x) * pitch,
+                    (film_y - 0.5 * self.image_height as f64) * pitch,
+                );
+                let (origin, direction) = lens.sample_ray(film, u_lens, sampler.get_1d())?;
+
+                let to_world = |l: Vector3| l[0] * self.u + l[1] * self.v - l[2] * self.w;
+                let ray_origin = self.center + eye_shift * self.u + to_world(origin);
+                Some(Ray::new(ray_origin, to_world(direction)))
+            }
+            _ => {
+                // Panoramic projections map film positions straight to directions, so they are
+                // always pinhole cameras.
+                let direction = self.panoramic_direction(film_x, film_y)?.unit_vector();
+                if eye_shift == 0.0 {
+                    return Some(Ray::new(self.center, direction));
+                }
+
+                // Equirectangular stereo is omni-directional: each direction is seen from the
+                // point on the interocular circle perpendicular to it, so the parallax is right
+                // all around. Other panoramas keep a fixed pair of eyes.
+                let side = match self.projection {
+                    Projection::Equirectangular => {
+                        let side = direction.cross(self.v);
+                        if side.near_zero() {
+                            Vector3::new(0.0, 0.0, 0.0)
+                        } else {
+                            side.unit_vector()
+                        }
+                    }
+                    _ => self.u,
+                };
+                let offset = eye_shift * side;
+                let target = self.convergence_distance() * direction;
+
+                Some(Ray::new(self.center + offset, target - offset))
+            }
         }
     }
 
-    fn sample_square() -> Vector3 {
-        let mut rnd = rand::thread_rng();
-        Vector3::new(rnd.gen::<f64>() - 0.5, rnd.gen::<f64>() - 0.5, 0.0)
+    // Direction for the film position (x, y) in pixels under a panoramic projection.
+    fn panoramic_direction(&self, x: f64, y: f64) -> Option<Vector3> {
+        let width = self.image_width as f64;
+        let height = self.image_height as f64;
+
+        match self.projection {
+            Projection::Equirectangular => {
+                // Longitude runs from -π at the left edge to π at the right, with the view
+                // direction in the middle. Polar angle runs from `vup` at the top edge down.
+                let phi = (x / width - 0.5) * 2.0 * PI;
+                let theta = y / height * PI;
+                let horizontal = phi.sin() * self.u - phi.cos() * self.w;
+                Some(theta.sin() * horizontal + theta.cos() * self.v)
+            }
+            Projection::Fisheye { fov, mapping } => {
+                let radius = 0.5 * width.min(height);
+                let px = (x - 0.5 * width) / radius;
+                let py = (0.5 * height - y) / radius;
+                let r = (px * px + py * py).sqrt();
+                if r > 1.0 {
+                    return None;
+                }
+
+                let half_fov = degrees_to_radians(fov) / 2.0;
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * half_fov,
+                    FisheyeMapping::Equisolid => 2.0 * (r * (half_fov / 2.0).sin()).asin(),
+                };
+                let phi = py.atan2(px);
+                let radial = phi.cos() * self.u + phi.sin() * self.v;
+                Some(theta.sin() * radial - theta.cos() * self.w)
+            }
+            Projection::Cubemap => {
+                let face_width = width / 3.0;
+                let face_height = height / 2.0;
+                let column = ((x / face_width) as usize).min(2);
+                let row = ((y / face_height) as usize).min(1);
+
+                // Face coordinates in [-1, 1], with b pointing up.
+                let a = 2.0 * (x / face_width - column as f64) - 1.0;
+                let b = 1.0 - 2.0 * (y / face_height - row as f64);
+
+                let (forward, right, up) = match (row, column) {
+                    (0, 0) => (self.u, self.w, self.v),
+                    (0, 1) => (-self.u, -self.w, self.v),
+                    (0, _) => (self.v, self.u, self.w),
+                    (_, 0) => (-self.v, self.u, -self.w),
+                    (_, 1) => (-self.w, self.u, self.v),
+                    (_, _) => (self.w, -self.u, self.v),
+                };
+                Some(forward + a * right + b * up)
+            }
+            Projection::Perspective
+            | Projection::Orthographic { .. }
+            | Projection::Realistic(_) => None,
+        }
     }
 
-    fn get_ray(&self, i: i32, j: i32) -> Ray {
-        // Construct a camera ray originating from the defocus disk and directed at a randomly
-        // sampled point around the pixel location i, j.
+    // Maps the 2D sample `u` to a point on the defocus disk centered at `center`, or nothing if
+    // the lens barrel blocks it as seen from the film position (x, y) in pixels.
+    pub fn defocus_disk_sample(
+        &self,
+        center: Vector3,
+        x: f64,
+        y: f64,
+        u: (f64, f64),
+    ) -> Option<Vector3> {
+        let (px, py) = self.aperture.sample(u);
+
+        if self.cat_eye > 0.0 {
+            // The barrel is modelled as a second unit disk, shifted further from the aperture
+            // the further the film position is from the image center.
+            let half_width = 0.5 * self.image_width as f64;
+            let half_height = 0.5 * self.image_height as f64;
+            let half_diagonal = (half_width * half_width + half_height * half_height).sqrt();
+            let shift = 2.0 * self.cat_eye / half_diagonal;
+            let bx = px + shift * (x - half_width);
+            let by = py + shift * (half_height - y);
+            if bx * bx + by * by > 1.0 {
+                return None;
+            }
+        }
+
+        Some(center + (px * self.defocus_disk_u) + (py * self.defocus_disk_v))
+    }
 
-        let offset = Camera::sample_square();
-        let pixel_sample = self.pixel00_loc
-            + (((i as f64) + offset[0]) * self.pixel_delta_u)
-            + (((j as f64) + offset[0]) * self.pixel_delta_v);
+    // Takes one sample of the output pixel, returning its color and filter weight. Samples
+    // without a camera ray are black but still count towards the weight.
+    fn sample_pixel(
+        &self,
+        output_pixel: (i32, i32),
+        world: &HittableList,
+        sampler: &mut dyn Sampler,
+    ) -> (Vector3, f64) {
+        let (i, j, eye_shift) = self.eye_pixel(output_pixel.0, output_pixel.1);
+        let (dx, dy, weight) = self.filter.sample(sampler.get_2d());
+        let u_time = sampler.get_1d();
+        let u_wavelength = sampler.get_1d();
+        let Some(mut ray) = self.get_ray(i, j, (dx, dy), eye_shift, sampler) else {
+            return (Vector3::new(0.0, 0.0, 0.0), weight);
+        };
 
-        let ray_origin = if self.defocus_angle <= 0.0 {
-            self.center
+        let (open, close) = self.shutter;
+        ray.time = self.frame + open + u_time * (close - open);
+        let color = if self.spectral {
+            let wavelengths = Wavelengths::sample(u_wavelength);
+            ray.wavelengths = Some(wavelengths);
+            wavelengths.to_rgb(ray_color(&ray, self.max_depth, world, sampler))
         } else {
-            self.defocus_disk_sample()
+            ray_color(&ray, self.max_depth, world, sampler)
         };
-        let ray_direction = pixel_sample - ray_origin;
 
-        Ray::new(ray_origin, ray_direction)
+        (color, weight)
     }
 
-    pub fn defocus_disk_sample(&self) -> Vector3 {
-        let p = Vector3::random_in_unit_disk();
-        self.center + (p[0] * self.defocus_disk_u) + (p[1] * self.defocus_disk_v)
+    // Renders the world as a PPM image to standard output.
+    pub fn render(&self, world: &HittableList) {
+        let mut out = BufWriter::new(io::stdout().lock());
+        self.render_to(world, &mut out)
+            .and_then(|()| out.flush())
+            .expect("failed to write image");
+    }
+
+    // Renders each frame in `frames` along the camera path into its own PPM file, named after
+    // `output_prefix` and the zero-padded frame number, e.g. `frame_0001.ppm`.
+    pub fn render_sequence(
+        &mut self,
+        world: &HittableList,
+        path: &CameraPath,
+        frames: RangeInclusive<i32>,
+        output_prefix: &str,
+    ) -> io::Result<()> {
+        for frame in frames {
+            let view = path.at(frame as f64);
+            self.set_view(view.look_from, view.look_at, view.v_fov, view.focus_dist);
+            self.set_frame(frame as f64);
+
+            let file_name = format!("{}{:04}.ppm", output_prefix, frame);
+            eprintln!("Rendering frame {} to {}", frame, file_name);
+            let mut out = BufWriter::new(File::create(&file_name)?);
+            self.render_to(world, &mut out)?;
+            out.flush()?;
+        }
+
+        Ok(())
     }
 
-    pub fn render(&self, world: HittableList) {
-        println!("P3");
-        println!("{} {}", self.image_width, self.image_height);
-        println!("255");
+    pub fn render_to(&self, world: &HittableList, out: &mut impl Write) -> io::Result<()> {
+        let (output_width, output_height) = self.output_size();
+        writeln!(out, "P3")?;
+        writeln!(out, "{} {}", output_width, output_height)?;
+        writeln!(out, "255")?;
+
+        let (min_samples, max_samples) = match &self.adaptive {
+            Some(adaptive) => {
+                let min_samples = adaptive.min_samples.max(1);
+                (min_samples, adaptive.max_samples.max(min_samples))
+            }
+            None => (self.samples_per_pixel, self.samples_per_pixel),
+        };
+        let exposure_scale = self.exposure.map_or(1.0, |exposure| exposure.scale());
+        let mut sampler = self.sampler.create(max_samples as u32, self.seed);
+        let mut sample_counts = Vec::with_capacity((output_width * output_height) as usize);
 
-        for j in 0..self.image_height {
-            eprint!("\rScanlines remaining: {}", self.image_height - j - 1);
+        for output_j in 0..output_height {
+            eprint!("\rScanlines remaining: {}", output_height - output_j - 1);
 
-            for i in 0..self.image_width {
+            for output_i in 0..output_width {
+                let output_pixel = (output_i, output_j);
                 let mut pixel_color = Vector3::new(0.0, 0.0, 0.0);
-                for _ in 0..self.samples_per_pixel {
-                    let ray = self.get_ray(i, j);
-                    pixel_color += ray_color(&ray, self.max_depth, &world);
+                let mut weight_sum = 0.0;
+                let mut statistics = PixelStatistics::default();
+                let mut sample_count = 0;
+                while sample_count < max_samples {
+                    sampler.start_pixel_sample(output_pixel, sample_count as u32);
+                    let (color, weight) = self.sample_pixel(output_pixel, world, &mut *sampler);
+                    pixel_color += weight * color;
+                    weight_sum += weight;
+                    sample_count += 1;
+
+                    // The noise is measured on the radiance alone, since the sign and size of
+                    // filter weights would add variance that more samples don't remove.
+                    let color = exposure_scale * color;
+                    statistics.push(0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]);
+                    if let Some(adaptive) = &self.adaptive {
+                        if sample_count >= min_samples
+                            && statistics.relative_error() < adaptive.threshold
+                        {
+                            break;
+                        }
+                    }
                 }
-                write_color(self.pixel_samples_scale * pixel_color);
+                sample_counts.push(sample_count);
+
+                // Filters with negative lobes can leave a pixel with no net weight.
+                let pixel_color = if weight_sum > 0.0 {
+                    pixel_color / weight_sum
+                } else {
+                    Vector3::new(0.0, 0.0, 0.0)
+                };
+                write_color(out, exposure_scale * pixel_color)?;
             }
         }
 
+        if let Some(path) = self.adaptive.as_ref().and_then(|a| a.heatmap.as_ref()) {
+            let mut heatmap = BufWriter::new(File::create(path)?);
+            writeln!(heatmap, "P3")?;
+            writeln!(heatmap, "{} {}", output_width, output_height)?;
+            writeln!(heatmap, "255")?;
+            let range = (max_samples - min_samples).max(1) as f64;
+            for count in sample_counts {
+                let level = (255.0 * (count - min_samples) as f64 / range).round() as u8;
+                writeln!(heatmap, "{} {} {}", level, level, level)?;
+            }
+            heatmap.flush()?;
+        }
+
         eprintln!("\nDone.");
+        Ok(())
+    }
+}
+
+// Rotates `vector` by `angle` radians about the unit `axis`, using Rodrigues' formula.
+fn rotate_about(vector: Vector3, axis: Vector3, angle: f64) -> Vector3 {
+    let (sin, cos) = angle.sin_cos();
+    vector * cos + axis.cross(vector) * sin + axis * axis.dot(vector) * (1.0 - cos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clearing_exposure_restores_defocus_angle() {
+        let mut camera = Camera::new(
+            64,
+            1.5,
+            1,
+            1,
+            20.0,
+            Vector3::new(13.0, 2.0, 3.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            0.6,
+            10.0,
+        );
+        camera.set_exposure(Some(Exposure {
+            f_number: 1.4,
+            shutter_time: 1.0 / 125.0,
+            iso: 100.0,
+            compensation: 0.0,
+        }));
+        assert!((camera.defocus_angle - 0.6).abs() > 1e-3);
+
+        camera.set_exposure(None);
+        assert_eq!(camera.defocus_angle, 0.6);
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_early_on_constant_pixels() {
+        let (width, height) = (16, 8);
+        let mut camera = Camera::new(
+            width,
+            2.0,
+            1,
+            1,
+            20.0,
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -1.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            0.0,
+            1.0,
+        );
+        // Parallel rays into an empty world all see the same sky. The negative lobes of the
+        // Mitchell filter must not count as noise.
+        camera.set_projection(Projection::Orthographic {
+            viewport_height: 1.0,
+        });
+        camera.set_filter(Filter::new(
+            FilterKind::Mitchell {
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            2.0,
+        ));
+        let heatmap = std::env::temp_dir().join("raytracer_adaptive_heatmap_test.ppm");
+        camera.set_adaptive(Some(AdaptiveSampling {
+            min_samples: 4,
+            max_samples: 64,
+            threshold: 0.01,
+            heatmap: Some(heatmap.to_string_lossy().into_owned()),
+        }));
+
+        let mut out = Vec::new();
+        camera
+            .render_to(&HittableList::default(), &mut out)
+            .unwrap();
+
+        let text = std::fs::read_to_string(&heatmap).unwrap();
+        std::fs::remove_file(&heatmap).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some("P3"));
+        assert_eq!(lines.next(), Some(format!("{} {}", width, height).as_str()));
+        assert_eq!(lines.next(), Some("255"));
+        let levels: Vec<&str> = lines.collect();
+        assert_eq!(levels.len(), (width * height) as usize);
+        // Black is `min_samples`.
+        assert!(levels.iter().all(|level| *level == "0 0 0"));
     }
 }
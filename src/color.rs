@@ -1,6 +1,9 @@
+use std::io::{self, Write};
+
 use crate::{
     hittable::{Hittable, HittableList},
     ray::Ray,
+    sampler::Sampler,
     vec3::Vector3,
 };
 
@@ -12,7 +15,7 @@ pub fn linear_to_gamma(linear_component: f64) -> f64 {
     }
 }
 
-pub fn write_color(pixel_color: Vector3) {
+pub fn write_color(out: &mut impl Write, pixel_color: Vector3) -> io::Result<()> {
     let r = linear_to_gamma(pixel_color[0]);
     let g = linear_to_gamma(pixel_color[1]);
     let b = linear_to_gamma(pixel_color[2]);
@@ -21,23 +24,34 @@ pub fn write_color(pixel_color: Vector3) {
     let ig = (256.0 * g.clamp(0.0, 0.999)) as u64;
     let ib = (256.0 * b.clamp(0.0, 0.999)) as u64;
 
-    println!("{} {} {}", ir, ig, ib);
+    writeln!(out, "{} {} {}", ir, ig, ib)
 }
 
-pub fn ray_color(ray: &Ray, depth: i32, world: &HittableList) -> Vector3 {
+pub fn ray_color(
+    ray: &Ray,
+    depth: i32,
+    world: &HittableList,
+    sampler: &mut dyn Sampler,
+) -> Vector3 {
     if depth <= 0 {
         return Vector3::new(0.0, 0.0, 0.0);
     }
 
     if let Some(hit_record) = world.hit(ray, 0.001, f64::INFINITY) {
-        return match hit_record.material.scatter(ray, &hit_record) {
-            Some((attenuation, scattered)) => attenuation * ray_color(&scattered, depth - 1, world),
+        return match hit_record.material.scatter(ray, &hit_record, sampler) {
+            Some((attenuation, scattered)) => {
+                attenuation * ray_color(&scattered, depth - 1, world, sampler)
+            }
             None => Vector3::new(0.0, 0.0, 0.0),
         };
     }
 
     let unit_direction = ray.direction.unit_vector();
     let a = 0.5 * (unit_direction[1] + 1.0);
+    let background = (1.0 - a) * Vector3::new(1.0, 1.0, 1.0) + a * Vector3::new(0.5, 0.7, 1.0);
 
-    (1.0 - a) * Vector3::new(1.0, 1.0, 1.0) + a * Vector3::new(0.5, 0.7, 1.0)
+    match ray.wavelengths {
+        Some(wavelengths) => wavelengths.upsample(background),
+        None => background,
+    }
 }
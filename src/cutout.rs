@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use crate::{
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+    sampler::hashed_uniform,
+    texture::Texture,
+};
+
+pub enum AlphaMode {
+    // Surfaces with an alpha below the threshold are cut away.
+    Threshold(f64),
+    // Rays pass through with probability one minus alpha, which keeps soft mask edges.
+    Stochastic,
+}
+
+// Masks out parts of an object with the first channel of a texture used as alpha, so that a
+// single quad can render as a leaf or a fence. Rays carry on through masked areas and may hit
+// the object again further along.
+pub struct Cutout {
+    object: Box<dyn Hittable>,
+    alpha: Arc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+impl Cutout {
+    pub fn new(
+        object: impl Hittable + 'static,
+        alpha: Arc<dyn Texture>,
+        mode: AlphaMode,
+    ) -> Cutout {
+        Cutout {
+            object: Box::new(object),
+            alpha,
+            mode,
+        }
+    }
+}
+
+impl Hittable for Cutout {
+    fn hit(&self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let mut closest_masked = ray_tmin;
+
+        loop {
+            let rec = self.object.hit(r, closest_masked, ray_tmax)?;
+            let alpha = self.alpha.value(rec.u, rec.v, rec.p)[0];
+            let opaque = match self.mode {
+                AlphaMode::Threshold(threshold) => alpha >= threshold,
+                // Hashing the ray and hit distance keeps the decision the same for the same
+                // ray, without needing a sampler inside intersection.
+                AlphaMode::Stochastic => {
+                    alpha >= 1.0
+                        || hashed_uniform(&[
+                            r.origin[0].to_bits(),
+                            r.origin[1].to_bits(),
+                            r.origin[2].to_bits(),
+                            r.direction[0].to_bits(),
+                            r.direction[1].to_bits(),
+                            r.direction[2].to_bits(),
+                            rec.t.to_bits(),
+                        ]) < alpha
+                }
+            };
+
+            if opaque {
+                return Some(rec);
+            }
+            closest_masked = rec.t;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// Piecewise-constant distribution over [0, 1) built from tabulated non-negative values, sampled by
+// inverting its cumulative distribution.
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Distribution1D {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f64;
+        }
+
+        // Fall back to a uniform distribution when every value is zero.
+        let integral = cdf[n];
+        if integral == 0.0 {
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n as f64;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= integral;
+            }
+        }
+
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // Maps `u` in [0, 1) to a point in [0, 1), returning it with its density and the index of
+    // the segment it falls in.
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        // Find the last cdf entry that is at or below u.
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+
+        let mut du = u - self.cdf[index];
+        let width = self.cdf[index + 1] - self.cdf[index];
+        if width > 0.0 {
+            du /= width;
+        }
+
+        let pdf = if self.integral > 0.0 {
+            self.func[index] / self.integral
+        } else {
+            1.0
+        };
+
+        ((index as f64 + du) / self.count() as f64, pdf, index)
+    }
+}
+
+// Piecewise-constant distribution over [0, 1)², stored as a marginal distribution over rows and
+// a conditional distribution within each row.
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // `func` holds `nu` values per row for `nv` rows.
+    pub fn new(func: &[f64], nu: usize, nv: usize) -> Distribution2D {
+        assert_eq!(func.len(), nu * nv);
+        let conditional: Vec<Distribution1D> = func
+            .chunks(nu)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    // Maps a pair of uniform samples to a point in [0, 1)², returning it with its density.
+    pub fn sample_continuous(&self, u: (f64, f64)) -> ((f64, f64), f64) {
+        let (y, pdf_y, row) = self.marginal.sample_continuous(u.1);
+        let (x, pdf_x, _) = self.conditional[row].sample_continuous(u.0);
+        ((x, y), pdf_x * pdf_y)
+    }
+}
@@ -0,0 +1,121 @@
+use std::f64::consts::PI;
+
+use crate::distribution::Distribution1D;
+
+// Number of bins the filter is tabulated in across its width, for importance sampling.
+const FILTER_TABLE_SIZE: usize = 128;
+
+// Shape of a pixel reconstruction filter. All are separable, f(x, y) = f(x)·f(y).
+#[derive(Clone, Copy)]
+pub enum FilterKind {
+    // Equal weight over the whole radius. A radius of half a pixel averages each pixel's area.
+    Box,
+    // Weight falling off linearly to zero at the radius.
+    Tent,
+    // Gaussian with standard deviation `sigma` in pixels, shifted to reach zero at the radius.
+    Gaussian { sigma: f64 },
+    // Mitchell–Netravali cubic, with B = C = 1/3 being the usual compromise between blurring
+    // and ringing.
+    Mitchell { b: f64, c: f64 },
+    // Sinc windowed by a wider sinc, with as many lobes as the radius in pixels. Sharpest, but
+    // rings around edges.
+    Lanczos,
+}
+
+// Pixel reconstruction filter. Samples are placed around the pixel center in proportion to the
+// magnitude of the filter, and weighted by its sign, so that a pixel is the weighted average of
+// its samples.
+pub struct Filter {
+    kind: FilterKind,
+    radius: f64,
+    distribution: Distribution1D,
+    signs: Vec<f64>,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, radius: f64) -> Filter {
+        let values: Vec<f64> = (0..FILTER_TABLE_SIZE)
+            .map(|i| {
+                let x = ((i as f64 + 0.5) / FILTER_TABLE_SIZE as f64 * 2.0 - 1.0) * radius;
+                evaluate(kind, radius, x)
+            })
+            .collect();
+        let signs = values.iter().map(|v| v.signum()).collect();
+        let distribution = Distribution1D::new(values.iter().map(|v| v.abs()).collect());
+
+        Filter {
+            kind,
+            radius,
+            distribution,
+            signs,
+        }
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    // Value of the filter at the offset (x, y) in pixels from the pixel center.
+    pub fn evaluate(&self, x: f64, y: f64) -> f64 {
+        evaluate(self.kind, self.radius, x) * evaluate(self.kind, self.radius, y)
+    }
+
+    // Maps a uniform 2D sample to an offset from the pixel center in pixels, returning it with
+    // the weight of the sample, which is -1 in negative lobes of the filter and 1 elsewhere.
+    pub fn sample(&self, u: (f64, f64)) -> (f64, f64, f64) {
+        let (x, wx) = self.sample_1d(u.0);
+        let (y, wy) = self.sample_1d(u.1);
+        (x, y, wx * wy)
+    }
+
+    fn sample_1d(&self, u: f64) -> (f64, f64) {
+        let (x, _, index) = self.distribution.sample_continuous(u);
+        ((2.0 * x - 1.0) * self.radius, self.signs[index])
+    }
+}
+
+fn evaluate(kind: FilterKind, radius: f64, x: f64) -> f64 {
+    let x = x.abs();
+    if x > radius {
+        return 0.0;
+    }
+
+    match kind {
+        FilterKind::Box => 1.0,
+        FilterKind::Tent => radius - x,
+        FilterKind::Gaussian { sigma } => {
+            let gaussian = |d: f64| (-d * d / (2.0 * sigma * sigma)).exp();
+            (gaussian(x) - gaussian(radius)).max(0.0)
+        }
+        FilterKind::Mitchell { b, c } => {
+            // The cubic is defined over [-2, 2], stretched here to the radius.
+            let x = 2.0 * x / radius;
+            if x > 1.0 {
+                ((-b - 6.0 * c) * x * x * x
+                    + (6.0 * b + 30.0 * c) * x * x
+                    + (-12.0 * b - 48.0 * c) * x
+                    + (8.0 * b + 24.0 * c))
+                    / 6.0
+            } else {
+                ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                    + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                    + (6.0 - 2.0 * b))
+                    / 6.0
+            }
+        }
+        FilterKind::Lanczos => sinc(x) * sinc(x / radius),
+    }
+}
+
+// Normalized sinc, sin(πx)/(πx).
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
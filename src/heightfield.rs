@@ -0,0 +1,412 @@
+use std::sync::Arc;
+
+use crate::{
+    hittable::{HitRecord, Hittable},
+    image::Image,
+    materials::Scatterable,
+    ray::Ray,
+    vec3::Vector3,
+};
+
+// Terrain surface sampled on a regular grid over the XZ plane. Each grid cell is split into two
+// triangles, and shading normals are interpolated from per-sample normals so the terrain looks
+// smooth regardless of the grid resolution.
+pub struct Heightfield {
+    heights: Vec<f64>,     // Samples in row-major order, rows running along +Z
+    normals: Vec<Vector3>, // Per-sample normals estimated from central differences
+    nx: usize,             // Sample count along X
+    nz: usize,             // Sample count along Z
+    origin: Vector3,       // Corner of the grid with the lowest X and Z at height zero
+    size: Vector3,         // Extent along X and Z, and the Y scale applied to every height
+    bbox_min: Vector3,
+    bbox_max: Vector3,
+    material: Arc<dyn Scatterable>,
+}
+
+impl Heightfield {
+    pub fn new(
+        heights: Vec<f64>,
+        nx: usize,
+        nz: usize,
+        origin: Vector3,
+        size: Vector3,
+        material: Arc<dyn Scatterable>,
+    ) -> Heightfield {
+        assert!(
+            nx >= 2 && nz >= 2,
+            "a heightfield needs at least 2x2 samples"
+        );
+        assert_eq!(heights.len(), nx * nz);
+
+        let min_height = heights.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max_height = heights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+        // Pad the box vertically so that perfectly flat terrain still has some thickness.
+        let padding = 1e-6 * size[1].abs().max(1.0);
+        let bbox_min = Vector3::new(
+            origin[0],
+            origin[1] + (min_height * size[1]).min(max_height * size[1]) - padding,
+            origin[2],
+        );
+        let bbox_max = Vector3::new(
+            origin[0] + size[0],
+            origin[1] + (min_height * size[1]).max(max_height * size[1]) + padding,
+            origin[2] + size[2],
+        );
+
+        let mut heightfield = Heightfield {
+            heights,
+            normals: Vec::new(),
+            nx,
+            nz,
+            origin,
+            size,
+            bbox_min,
+            bbox_max,
+            material,
+        };
+        heightfield.normals = heightfield.compute_normals();
+        heightfield
+    }
+
+    // Builds a heightfield from a grayscale raster, e.g. a DEM exported as 16-bit PGM. Image rows
+    // map to +Z and columns to +X; sample values in [0, 1] are scaled by `size[1]`. Texture
+    // coordinates follow the raster, so an `ImageTexture` of the same size drapes over it.
+    pub fn from_image(
+        image: &Image,
+        origin: Vector3,
+        size: Vector3,
+        material: Arc<dyn Scatterable>,
+    ) -> Heightfield {
+        let (nx, nz) = (image.width(), image.height());
+        let mut heights = Vec::with_capacity(nx * nz);
+        for z in 0..nz {
+            for x in 0..nx {
+                heights.push(image.luminance(x, z));
+            }
+        }
+
+        Heightfield::new(heights, nx, nz, origin, size, material)
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (
+            self.size[0] / (self.nx - 1) as f64,
+            self.size[2] / (self.nz - 1) as f64,
+        )
+    }
+
+    fn height(&self, x: usize, z: usize) -> f64 {
+        self.heights[z * self.nx + x]
+    }
+
+    fn vertex(&self, x: usize, z: usize) -> Vector3 {
+        let (cell_x, cell_z) = self.cell_size();
+        self.origin
+            + Vector3::new(
+                x as f64 * cell_x,
+                self.height(x, z) * self.size[1],
+                z as f64 * cell_z,
+            )
+    }
+
+    fn compute_normals(&self) -> Vec<Vector3> {
+        let (cell_x, cell_z) = self.cell_size();
+        let mut normals = Vec::with_capacity(self.nx * self.nz);
+
+        for z in 0..self.nz {
+            for x in 0..self.nx {
+                let (x0, x1) = (x.saturating_sub(1), (x + 1).min(self.nx - 1));
+                let (z0, z1) = (z.saturating_sub(1), (z + 1).min(self.nz - 1));
+
+                let dh_dx = (self.height(x1, z) - self.height(x0, z)) * self.size[1]
+                    / ((x1 - x0) as f64 * cell_x);
+                let dh_dz = (self.height(x, z1) - self.height(x, z0)) * self.size[1]
+                    / ((z1 - z0) as f64 * cell_z);
+
+                normals.push(Vector3::new(-dh_dx, 1.0, -dh_dz).unit_vector());
+            }
+        }
+
+        normals
+    }
+
+    fn hit_cell(
+        &self,
+        r: &Ray,
+        x: usize,
+        z: usize,
+        ray_tmin: f64,
+        ray_tmax: f64,
+    ) -> Option<HitRecord> {
+        let corners = [(x, z), (x + 1, z), (x + 1, z + 1), (x, z + 1)];
+        let triangles = [[0, 1, 2], [0, 2, 3]];
+
+        let mut closest: Option<(f64, [usize; 3], f64, f64)> = None;
+        let mut closest_so_far = ray_tmax;
+        for triangle in triangles {
+            let [a, b, c] = triangle.map(|i| self.vertex(corners[i].0, corners[i].1));
+            if let Some((t, b1, b2)) = intersect_triangle(r, a, b, c, ray_tmin, closest_so_far) {
+                closest_so_far = t;
+                closest = Some((t, triangle, b1, b2));
+            }
+        }
+
+        let (t, triangle, b1, b2) = closest?;
+        let [na, nb, nc] = triangle.map(|i| self.normals[corners[i].1 * self.nx + corners[i].0]);
+        let outward_normal = ((1.0 - b1 - b2) * na + b1 * nb + b2 * nc).unit_vector();
+
+        let mut rec = HitRecord::new(
+            r.at(t),
+            Vector3::new(0.0, 0.0, 0.0),
+            t,
+            self.material.clone(),
+        );
+        rec.set_face_normal(r, outward_normal);
+        // V runs from 1 at the first row to 0 at the last, as `ImageTexture` reads images top down.
+        rec.u = (rec.p[0] - self.origin[0]) / self.size[0];
+        rec.v = 1.0 - (rec.p[2] - self.origin[2]) / self.size[2];
+
+        // Tangents of the smooth surface y = f(x, z) described by the interpolated normal.
+        let slope_x = -outward_normal[0] / outward_normal[1];
+        let slope_z = -outward_normal[2] / outward_normal[1];
+        rec.dpdu = self.size[0] * Vector3::new(1.0, slope_x, 0.0);
+        rec.dpdv = -self.size[2] * Vector3::new(0.0, slope_z, 1.0);
+
+        Some(rec)
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        // Clip the ray against the bounding box of the terrain.
+        let mut t_enter = ray_tmin;
+        let mut t_exit = ray_tmax;
+        for axis in 0..3 {
+            let inv_d = 1.0 / r.direction[axis];
+            let mut t0 = (self.bbox_min[axis] - r.origin[axis]) * inv_d;
+            let mut t1 = (self.bbox_max[axis] - r.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_enter = t_enter.max(t0);
+            t_exit = t_exit.min(t1);
+            if t_exit < t_enter {
+                return None;
+            }
+        }
+
+        // Walk the cells crossed by the ray in front-to-back order with a 2D DDA. The triangles of
+        // a cell never leave its footprint, so the first cell with a hit holds the closest one.
+        let (cell_x, cell_z) = self.cell_size();
+        let entry = r.at(t_enter);
+        let last_x = self.nx as isize - 2;
+        let last_z = self.nz as isize - 2;
+        let mut x = (((entry[0] - self.origin[0]) / cell_x).floor() as isize).clamp(0, last_x);
+        let mut z = (((entry[2] - self.origin[2]) / cell_z).floor() as isize).clamp(0, last_z);
+
+        let setup_axis = |axis: usize, cell: isize, cell_size: f64| -> (isize, f64, f64) {
+            let d = r.direction[axis];
+            if d > 0.0 {
+                let boundary = self.origin[axis] + (cell + 1) as f64 * cell_size;
+                (1, (boundary - r.origin[axis]) / d, cell_size / d)
+            } else if d < 0.0 {
+                let boundary = self.origin[axis] + cell as f64 * cell_size;
+                (-1, (boundary - r.origin[axis]) / d, -cell_size / d)
+            } else {
+                (0, f64::INFINITY, f64::INFINITY)
+            }
+        };
+        let (step_x, mut t_next_x, t_delta_x) = setup_axis(0, x, cell_x);
+        let (step_z, mut t_next_z, t_delta_z) = setup_axis(2, z, cell_z);
+
+        loop {
+            if let Some(rec) = self.hit_cell(r, x as usize, z as usize, ray_tmin, ray_tmax) {
+                return Some(rec);
+            }
+
+            if t_next_x < t_next_z {
+                if t_next_x > t_exit {
+                    return None;
+                }
+                x += step_x;
+                t_next_x += t_delta_x;
+            } else {
+                if t_next_z > t_exit {
+                    return None;
+                }
+                z += step_z;
+                t_next_z += t_delta_z;
+            }
+
+            if x < 0 || x > last_x || z < 0 || z > last_z {
+                return None;
+            }
+        }
+    }
+}
+
+// Möller–Trumbore ray/triangle intersection. Returns the ray parameter and the barycentric
+// coordinates of the hit relative to `b` and `c`.
+fn intersect_triangle(
+    r: &Ray,
+    a: Vector3,
+    b: Vector3,
+    c: Vector3,
+    ray_tmin: f64,
+    ray_tmax: f64,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = r.direction.cross(edge2);
+    let det = edge1.dot(p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let s = r.origin - a;
+    let b1 = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let b2 = r.direction.dot(q) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inv_det;
+    if t <= ray_tmin || ray_tmax <= t {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::{
+        materials::Lambertian,
+        pcg::Pcg32,
+        texture::{ImageTexture, Texture},
+    };
+
+    fn material() -> Arc<dyn Scatterable> {
+        Arc::new(Lambertian::new(Vector3::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn texture_from_the_same_raster_lines_up() {
+        let (width, height) = (6, 4);
+        let samples: Vec<f64> = (0..width * height)
+            .map(|i| i as f64 / (width * height) as f64)
+            .collect();
+        let origin = Vector3::new(-1.0, 0.0, -2.0);
+        let size = Vector3::new(3.0, 1.0, 5.0);
+        let heightfield = Heightfield::from_image(
+            &Image::new(width, height, 1, samples.clone()),
+            origin,
+            size,
+            material(),
+        );
+        let texture = ImageTexture::linear(Image::new(width, height, 1, samples));
+
+        // Drop rays onto each sample, nudged into the terrain so nearest-texel lookup is exact.
+        let nudge = |i: usize, n: usize| if i + 1 < n { 1e-3 } else { -1e-3 };
+        for z in 0..height {
+            for x in 0..width {
+                let target = Vector3::new(
+                    origin[0] + size[0] * (x as f64 + nudge(x, width)) / (width - 1) as f64,
+                    0.0,
+                    origin[2] + size[2] * (z as f64 + nudge(z, height)) / (height - 1) as f64,
+                );
+                let ray = Ray::new(
+                    target + Vector3::new(0.0, 10.0, 0.0),
+                    Vector3::new(0.0, -1.0, 0.0),
+                );
+                let rec = heightfield.hit(&ray, 0.001, f64::INFINITY).unwrap();
+                let terrain = (rec.p[1] - origin[1]) / size[1];
+                let draped = texture.value(rec.u, rec.v, rec.p)[0];
+                assert!(
+                    (terrain - draped).abs() < 0.01,
+                    "terrain {} but texture {} at sample ({}, {})",
+                    terrain,
+                    draped,
+                    x,
+                    z
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn grid_traversal_matches_brute_force() {
+        let (nx, nz) = (12, 9);
+        let heights = (0..nx * nz)
+            .map(|i| {
+                let (x, z) = ((i % nx) as f64, (i / nx) as f64);
+                0.5 + 0.3 * (0.9 * x).sin() * (1.3 * z).cos()
+            })
+            .collect();
+        let origin = Vector3::new(-2.0, -1.0, -3.0);
+        let size = Vector3::new(4.0, 2.0, 6.0);
+        let heightfield = Heightfield::new(heights, nx, nz, origin, size, material());
+
+        let mut rng = Pcg32::new(7, 0);
+        let mut crossings = 0;
+        for _ in 0..500 {
+            // Shallow rays from above the terrain that cross many cells before landing.
+            let from = Vector3::new(
+                rng.gen_range(-2.0..2.0),
+                rng.gen_range(0.7..2.0),
+                rng.gen_range(-3.0..3.0),
+            );
+            let to = Vector3::new(
+                rng.gen_range(-2.0..2.0),
+                rng.gen_range(-1.0..0.5),
+                rng.gen_range(-3.0..3.0),
+            );
+            let ray = Ray::new(from, (to - from).unit_vector());
+
+            let mut expected: Option<HitRecord> = None;
+            for z in 0..nz - 1 {
+                for x in 0..nx - 1 {
+                    let tmax = expected.as_ref().map_or(f64::INFINITY, |rec| rec.t);
+                    if let Some(rec) = heightfield.hit_cell(&ray, x, z, 0.001, tmax) {
+                        expected = Some(rec);
+                    }
+                }
+            }
+
+            let actual = heightfield.hit(&ray, 0.001, f64::INFINITY);
+            match (&expected, &actual) {
+                (Some(expected), Some(actual)) => {
+                    assert!((expected.t - actual.t).abs() < 1e-9);
+                    let cells_x =
+                        ((actual.p[0] - from[0]).abs() * (nx - 1) as f64 / size[0]) as i32;
+                    let cells_z =
+                        ((actual.p[2] - from[2]).abs() * (nz - 1) as f64 / size[2]) as i32;
+                    if cells_x + cells_z >= 3 {
+                        crossings += 1;
+                    }
+                }
+                (None, None) => {}
+                _ => panic!(
+                    "traversal found {:?} but brute force found {:?}",
+                    actual.map(|r| r.t),
+                    expected.map(|r| r.t)
+                ),
+            }
+        }
+        assert!(
+            crossings > 100,
+            "only {} rays crossed several cells",
+            crossings
+        );
+    }
+}
@@ -1,11 +1,16 @@
 use std::sync::Arc;
 
-use crate::{materials::Scatterable, ray::Ray, vec3::Vector3};
+use crate::{materials::Scatterable, onb::Onb, ray::Ray, vec3::Vector3};
 
+#[derive(Clone)]
 pub struct HitRecord {
     pub p: Vector3,
     pub normal: Vector3,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
+    pub dpdu: Vector3, // Surface tangent along increasing u
+    pub dpdv: Vector3, // Surface tangent along increasing v
     pub front_face: bool,
     pub material: Arc<dyn Scatterable>,
 }
@@ -16,6 +21,10 @@ impl HitRecord {
             p,
             normal,
             t,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vector3::new(0.0, 0.0, 0.0),
+            dpdv: Vector3::new(0.0, 0.0, 0.0),
             front_face: false,
             material,
         }
@@ -29,6 +38,25 @@ impl HitRecord {
             self.normal = -outward_normal;
         }
     }
+
+    // Normal pointing out of the surface, regardless of the side the ray came from.
+    pub fn outward_normal(&self) -> Vector3 {
+        if self.front_face {
+            self.normal
+        } else {
+            -self.normal
+        }
+    }
+
+    // Local shading frame around the normal, aligned with dpdu when the surface provides it so
+    // that anisotropic materials have a well defined tangent direction.
+    pub fn shading_frame(&self) -> Onb {
+        if self.dpdu.near_zero() {
+            Onb::new(self.normal)
+        } else {
+            Onb::from_tangent(self.normal, self.dpdu)
+        }
+    }
 }
 
 pub trait Hittable {
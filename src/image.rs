@@ -0,0 +1,174 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use crate::vec3::Vector3;
+
+// Raster loaded from a Netpbm file (PGM or PPM, ASCII or binary). Samples are normalized to
+// [0, 1] and stored as-is, without any gamma decoding.
+pub struct Image {
+    width: usize,
+    height: usize,
+    channels: usize,
+    data: Vec<f64>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, channels: usize, data: Vec<f64>) -> Image {
+        assert_eq!(data.len(), width * height * channels);
+        Image {
+            width,
+            height,
+            channels,
+            data,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Image> {
+        let bytes = fs::read(path)?;
+        Image::parse(&bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Image> {
+        let mut pos = 0;
+        let magic = next_token(bytes, &mut pos)?;
+        let (channels, binary) = match magic.as_str() {
+            "P2" => (1, false),
+            "P3" => (3, false),
+            "P5" => (1, true),
+            "P6" => (3, true),
+            _ => return Err(invalid_data("unsupported Netpbm format")),
+        };
+
+        let width = parse_number(bytes, &mut pos)?;
+        let height = parse_number(bytes, &mut pos)?;
+        let max_value = parse_number(bytes, &mut pos)?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(invalid_data("invalid maximum sample value"));
+        }
+
+        // The header comes from the file, so guard the raster size against overflow and only
+        // reserve memory once the file is known to hold that many samples.
+        let count = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(channels))
+            .ok_or_else(|| invalid_data("image dimensions are too large"))?;
+        let data = if binary {
+            // A single whitespace byte separates the header from the raster.
+            pos += 1;
+            let sample_size = if max_value < 256 { 1 } else { 2 };
+            let raster = count
+                .checked_mul(sample_size)
+                .and_then(|length| bytes.get(pos..pos.checked_add(length)?))
+                .ok_or_else(|| invalid_data("truncated raster"))?;
+            raster
+                .chunks_exact(sample_size)
+                .map(|sample| {
+                    let value = if sample_size == 1 {
+                        sample[0] as usize
+                    } else {
+                        ((sample[0] as usize) << 8) | sample[1] as usize
+                    };
+                    value as f64 / max_value as f64
+                })
+                .collect()
+        } else {
+            // Every ASCII sample takes at least one byte.
+            if count > bytes.len() - pos {
+                return Err(invalid_data("truncated raster"));
+            }
+            let mut data = Vec::with_capacity(count);
+            for _ in 0..count {
+                data.push(parse_number(bytes, &mut pos)? as f64 / max_value as f64);
+            }
+            data
+        };
+
+        Ok(Image::new(width, height, channels, data))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Returns the pixel as an RGB triple; grayscale images replicate the single channel.
+    pub fn pixel(&self, x: usize, y: usize) -> Vector3 {
+        let x = x.min(self.width - 1);
+        let y = y.min(self.height - 1);
+        let index = (y * self.width + x) * self.channels;
+        if self.channels == 1 {
+            let value = self.data[index];
+            Vector3::new(value, value, value)
+        } else {
+            Vector3::new(self.data[index], self.data[index + 1], self.data[index + 2])
+        }
+    }
+
+    // Returns the pixel as a single value, averaging the channels of color images.
+    pub fn luminance(&self, x: usize, y: usize) -> f64 {
+        let pixel = self.pixel(x, y);
+        (pixel[0] + pixel[1] + pixel[2]) / 3.0
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn next_token(bytes: &[u8], pos: &mut usize) -> Result<String> {
+    // Skip whitespace and comments, which run until the end of the line.
+    while *pos < bytes.len() {
+        if bytes[*pos] == b'#' {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                *pos += 1;
+            }
+        } else if bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        } else {
+            break;
+        }
+    }
+
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    if start == *pos {
+        return Err(invalid_data("unexpected end of file"));
+    }
+
+    Ok(String::from_utf8_lossy(&bytes[start..*pos]).into_owned())
+}
+
+fn parse_number(bytes: &[u8], pos: &mut usize) -> Result<usize> {
+    next_token(bytes, pos)?
+        .parse()
+        .map_err(|_| invalid_data("expected a number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_overflowing_dimensions() {
+        let error = Image::parse(b"P5 4294967296 4294967296 255\n")
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_raster_larger_than_file() {
+        let error = Image::parse(b"P2 100000 100000 255\n0 1 2\n")
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
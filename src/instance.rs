@@ -0,0 +1,40 @@
+use crate::{
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+    transform::AnimatedTransform,
+};
+
+// Places an object in the world through a transform, which may be animated. Rays are moved into
+// the object's space at their own time, so moving objects blur when the shutter is open.
+pub struct Instance {
+    object: Box<dyn Hittable>,
+    transform: AnimatedTransform,
+}
+
+impl Instance {
+    pub fn new(object: impl Hittable + 'static, transform: AnimatedTransform) -> Instance {
+        Instance {
+            object: Box::new(object),
+            transform,
+        }
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        // The transform is affine, so the ray parameter t is the same in both spaces.
+        let transform = self.transform.at(r.time);
+        let local_ray = r.spawn(
+            transform.inverse_point(r.origin),
+            transform.inverse_vector(r.direction),
+        );
+
+        let mut rec = self.object.hit(&local_ray, ray_tmin, ray_tmax)?;
+        rec.p = transform.point(rec.p);
+        rec.normal = transform.normal(rec.normal).unit_vector();
+        rec.dpdu = transform.vector(rec.dpdu);
+        rec.dpdv = transform.vector(rec.dpdv);
+
+        Some(rec)
+    }
+}
@@ -0,0 +1,396 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use crate::vec3::Vector3;
+
+// Number of film radii for which the exit pupil is bounded, and the resolution of the grid of
+// rays traced over the rear element for each of them.
+const PUPIL_BINS: usize = 32;
+const PUPIL_FILM_SAMPLES: usize = 8;
+const PUPIL_GRID: usize = 32;
+
+#[derive(Clone, Copy)]
+struct LensElement {
+    curvature_radius: f64, // Signed radius of the surface, or zero for the aperture stop
+    thickness: f64,        // Distance along the axis to the next surface towards the film
+    eta: f64,              // Index of refraction of the medium behind the surface
+    aperture_radius: f64,  // Radius of the clear aperture of the surface
+}
+
+#[derive(Clone, Copy)]
+struct PupilBounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl PupilBounds {
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0).max(0.0) * (self.max.1 - self.min.1).max(0.0)
+    }
+}
+
+// Camera lens made of spherical surfaces, traced exactly instead of approximated by a thin lens,
+// so that distortion, focus breathing and optical vignetting come out of the optics.
+//
+// Lens space has the film at z = 0 and the optical axis along +z towards the scene, in meters.
+#[derive(Clone)]
+pub struct LensSystem {
+    elements: Vec<LensElement>, // Surfaces from the front of the lens to the rear
+    pupil_bounds: Vec<PupilBounds>, // Exit pupil bounds per film radius, once focused
+    largest_pupil_area: f64,    // Area of the largest of the exit pupil bounds
+    film_radius: f64,           // Half the film diagonal the exit pupil was bounded for
+}
+
+impl LensSystem {
+    pub fn load(path: impl AsRef<Path>) -> Result<LensSystem> {
+        let text = fs::read_to_string(path)?;
+        LensSystem::parse(&text)
+    }
+
+    // Parses a prescription with one surface per line, from the front of the lens to the rear:
+    // curvature radius, thickness to the next surface, index of refraction behind the surface
+    // and aperture diameter, all in millimeters. A radius of zero marks the aperture stop and
+    // an index of zero stands for air. Lines starting with `#` are comments.
+    pub fn parse(text: &str) -> Result<LensSystem> {
+        let mut elements = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(|token| token.parse::<f64>())
+                .collect::<std::result::Result<Vec<f64>, _>>()
+                .map_err(|_| invalid_data("expected a number"))?;
+            if values.len() != 4 {
+                return Err(invalid_data("expected four values per surface"));
+            }
+
+            elements.push(LensElement {
+                curvature_radius: values[0] / 1000.0,
+                thickness: values[1] / 1000.0,
+                eta: if values[2] == 0.0 { 1.0 } else { values[2] },
+                aperture_radius: values[3] / 2000.0,
+            });
+        }
+
+        if elements.is_empty() {
+            return Err(invalid_data("lens has no surfaces"));
+        }
+
+        Ok(LensSystem {
+            elements,
+            pupil_bounds: Vec::new(),
+            largest_pupil_area: 0.0,
+            film_radius: 0.0,
+        })
+    }
+
+    // Effective focal length in meters.
+    pub fn focal_length(&self) -> Option<f64> {
+        let (principal, focal) = self.cardinal_points(false)?;
+        Some(principal - focal)
+    }
+
+    // Returns the lens moved so that objects `focus_distance` meters from the film are in focus,
+    // with its exit pupil bounded for a film of the given diagonal.
+    pub fn focus(&self, focus_distance: f64, film_diagonal: f64) -> LensSystem {
+        let mut lens = self.clone();
+
+        // Moving the lens by delta shifts both principal planes by delta. Solve the thick lens
+        // equation 1/s_o + 1/s_i = 1/f for the shift, taking the root closest to the focal
+        // length for distant objects.
+        if let (Some((film_principal, film_focal)), Some((scene_principal, _))) =
+            (self.cardinal_points(false), self.cardinal_points(true))
+        {
+            let focal_length = film_principal - film_focal;
+            let a = focus_distance - scene_principal;
+            let b = film_principal;
+            let delta = 0.5 * ((a - b) - ((a + b) * (a + b - 4.0 * focal_length)).sqrt());
+            if delta.is_finite() {
+                let rear = lens.elements.len() - 1;
+                lens.elements[rear].thickness += delta;
+            }
+        }
+
+        lens.film_radius = film_diagonal / 2.0;
+        lens.pupil_bounds = (0..PUPIL_BINS)
+            .map(|i| {
+                let r0 = i as f64 / PUPIL_BINS as f64 * lens.film_radius;
+                let r1 = (i + 1) as f64 / PUPIL_BINS as f64 * lens.film_radius;
+                lens.bound_exit_pupil(r0, r1)
+            })
+            .collect();
+        lens.largest_pupil_area = lens
+            .pupil_bounds
+            .iter()
+            .map(|b| b.area())
+            .fold(0.0, f64::max);
+
+        lens
+    }
+
+    // Samples a ray leaving the front of the lens that reaches the given film point, returning
+    // its origin and direction in lens space. `u` picks the point on the exit pupil and `u_keep`
+    // decides whether the ray survives vignetting. Rays blocked inside the lens have no result,
+    // and neither does any ray of a lens that has not been through `focus`.
+    pub fn sample_ray(
+        &self,
+        film: (f64, f64),
+        u: (f64, f64),
+        u_keep: f64,
+    ) -> Option<(Vector3, Vector3)> {
+        if self.pupil_bounds.is_empty() {
+            return None;
+        }
+
+        let r = (film.0 * film.0 + film.1 * film.1).sqrt();
+        let bin = ((r / self.film_radius * PUPIL_BINS as f64) as usize).min(PUPIL_BINS - 1);
+        let bounds = self.pupil_bounds[bin];
+
+        // Bounds were computed for film points along +x, so rotate the sample to this point.
+        let sx = bounds.min.0 + u.0 * (bounds.max.0 - bounds.min.0);
+        let sy = bounds.min.1 + u.1 * (bounds.max.1 - bounds.min.1);
+        let (sin, cos) = if r > 0.0 {
+            (film.1 / r, film.0 / r)
+        } else {
+            (0.0, 1.0)
+        };
+        let pupil = Vector3::new(cos * sx - sin * sy, sin * sx + cos * sy, self.rear_z());
+
+        let origin = Vector3::new(film.0, film.1, 0.0);
+        let direction = pupil - origin;
+
+        // The film receives light in proportion to the area of the exit pupil it sees and to
+        // cos⁴ of the angle of incidence. Keep rays with that probability, relative to the
+        // largest bounds, so that film points sampling smaller bounds come out darker.
+        let cos_theta = direction[2] / direction.length();
+        let keep = bounds.area() / self.largest_pupil_area * cos_theta.powi(4);
+        if u_keep >= keep {
+            return None;
+        }
+
+        self.trace(origin, direction, true)
+    }
+
+    fn rear_z(&self) -> f64 {
+        self.elements[self.elements.len() - 1].thickness
+    }
+
+    fn rear_aperture_radius(&self) -> f64 {
+        self.elements[self.elements.len() - 1].aperture_radius
+    }
+
+    // Position of each surface vertex along the axis.
+    fn vertices(&self) -> Vec<f64> {
+        let mut z = 0.0;
+        let mut vertices = vec![0.0; self.elements.len()];
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            z += element.thickness;
+            vertices[i] = z;
+        }
+        vertices
+    }
+
+    // Traces a ray through every surface, starting from the film side or from the scene side.
+    // Returns the ray leaving the last surface, or nothing if an aperture blocks it or it is
+    // totally internally reflected.
+    fn trace(
+        &self,
+        mut origin: Vector3,
+        mut direction: Vector3,
+        from_film: bool,
+    ) -> Option<(Vector3, Vector3)> {
+        let vertices = self.vertices();
+        let n = self.elements.len();
+        let medium_eta = |i: usize| {
+            if i == 0 {
+                1.0
+            } else {
+                self.elements[i - 1].eta
+            }
+        };
+
+        for step in 0..n {
+            let i = if from_film { n - 1 - step } else { step };
+            let element = self.elements[i];
+            let radius = element.curvature_radius;
+
+            let (t, normal) = if radius == 0.0 {
+                ((vertices[i] - origin[2]) / direction[2], None)
+            } else {
+                // Positive radii have their center of curvature towards the film.
+                let center = Vector3::new(0.0, 0.0, vertices[i] - radius);
+                let oc = origin - center;
+                let a = direction.length_squared();
+                let half_b = oc.dot(direction);
+                let c = oc.length_squared() - radius * radius;
+                let discriminant = half_b * half_b - a * c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let sqrtd = discriminant.sqrt();
+                let use_closer = (direction[2] > 0.0) != (radius > 0.0);
+                let t = if use_closer {
+                    (-half_b - sqrtd) / a
+                } else {
+                    (-half_b + sqrtd) / a
+                };
+                (t, Some(center))
+            };
+            if t.is_nan() || t <= 0.0 {
+                return None;
+            }
+
+            let p = origin + t * direction;
+            if p[0] * p[0] + p[1] * p[1] > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            origin = p;
+
+            if let Some(center) = normal {
+                let mut normal = (p - center).unit_vector();
+                if normal.dot(direction) > 0.0 {
+                    normal = -normal;
+                }
+                let (eta_i, eta_t) = if from_film {
+                    (element.eta, medium_eta(i))
+                } else {
+                    (medium_eta(i), element.eta)
+                };
+                direction = refract(direction.unit_vector(), normal, eta_i / eta_t)?;
+            }
+        }
+
+        Some((origin, direction))
+    }
+
+    // Traces a ray parallel to the axis through the lens and returns where the principal plane
+    // and focal point lie on the side it exits. Tracing from the film gives the points on the
+    // scene side, and tracing from the scene gives the points on the film side.
+    fn cardinal_points(&self, from_film: bool) -> Option<(f64, f64)> {
+        let height = 0.01
+            * self.elements[0]
+                .aperture_radius
+                .min(self.rear_aperture_radius());
+        let (origin, direction) = if from_film {
+            (Vector3::new(height, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0))
+        } else {
+            let front = self.vertices()[0];
+            (
+                Vector3::new(height, 0.0, front + 1.0),
+                Vector3::new(0.0, 0.0, -1.0),
+            )
+        };
+
+        let (o, d) = self.trace(origin, direction, from_film)?;
+        if d[0] == 0.0 {
+            return None;
+        }
+        let focal = o[2] + (-o[0] / d[0]) * d[2];
+        let principal = o[2] + ((height - o[0]) / d[0]) * d[2];
+        Some((principal, focal))
+    }
+
+    // Bounds on the rear element plane of the rays from film points between radii r0 and r1
+    // along +x that make it through the lens.
+    fn bound_exit_pupil(&self, r0: f64, r1: f64) -> PupilBounds {
+        let extent = 1.5 * self.rear_aperture_radius();
+        let spacing = 2.0 * extent / PUPIL_GRID as f64;
+        let rear_z = self.rear_z();
+
+        let mut min = (f64::INFINITY, f64::INFINITY);
+        let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for i in 0..PUPIL_FILM_SAMPLES {
+            let x = r0 + (i as f64 + 0.5) / PUPIL_FILM_SAMPLES as f64 * (r1 - r0);
+            let origin = Vector3::new(x, 0.0, 0.0);
+            for gy in 0..PUPIL_GRID {
+                for gx in 0..PUPIL_GRID {
+                    let px = -extent + (gx as f64 + 0.5) * spacing;
+                    let py = -extent + (gy as f64 + 0.5) * spacing;
+                    let direction = Vector3::new(px, py, rear_z) - origin;
+                    if self.trace(origin, direction, true).is_some() {
+                        min = (min.0.min(px), min.1.min(py));
+                        max = (max.0.max(px), max.1.max(py));
+                    }
+                }
+            }
+        }
+
+        if min.0 > max.0 {
+            // No ray got through, so nothing is sampled for these film points.
+            return PupilBounds {
+                min: (0.0, 0.0),
+                max: (0.0, 0.0),
+            };
+        }
+
+        // Grow the bounds by a grid cell, since rays between the grid points may pass as well.
+        PupilBounds {
+            min: (min.0 - spacing, min.1 - spacing),
+            max: (max.0 + spacing, max.1 + spacing),
+        }
+    }
+}
+
+// Refracts a unit direction through a surface with the given normal facing against it, or
+// returns nothing on total internal reflection.
+fn refract(direction: Vector3, normal: Vector3, eta: f64) -> Option<Vector3> {
+    let cos_i = -direction.dot(normal);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(eta * direction + (eta * cos_i - cos_t) * normal)
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn focuses_at_requested_distance() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/lenses/double_gauss_50mm.txt");
+        let lens = LensSystem::load(path).unwrap();
+
+        for focus_distance in [1.0, 3.0] {
+            let focused = lens.focus(focus_distance, 0.043);
+
+            // A paraxial ray from the center of the film crosses the axis again at the distance
+            // in focus.
+            let height = 0.05 * focused.rear_aperture_radius();
+            let origin = Vector3::new(0.0, 0.0, 0.0);
+            let direction = Vector3::new(height, 0.0, focused.rear_z());
+            let (exit, exit_direction) = focused.trace(origin, direction, true).unwrap();
+            let t = -exit[0] / exit_direction[0];
+            let z = exit[2] + t * exit_direction[2];
+
+            assert!(
+                (z - focus_distance).abs() < 0.02 * focus_distance,
+                "focused at {} instead of {}",
+                z,
+                focus_distance
+            );
+        }
+    }
+
+    #[test]
+    fn unfocused_lens_samples_no_rays() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/lenses/double_gauss_50mm.txt");
+        let lens = LensSystem::load(path).unwrap();
+
+        assert!(lens.sample_ray((0.01, 0.0), (0.5, 0.5), 0.0).is_none());
+        let focused = lens.focus(3.0, 0.043);
+        assert!(focused.sample_ray((0.01, 0.0), (0.5, 0.5), 0.0).is_some());
+    }
+}
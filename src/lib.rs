@@ -0,0 +1,25 @@
+pub mod animation;
+pub mod aperture;
+pub mod camera;
+pub mod color;
+pub mod cutout;
+pub mod distribution;
+pub mod filter;
+pub mod heightfield;
+pub mod hittable;
+pub mod image;
+pub mod instance;
+pub mod lens;
+pub mod materials;
+pub mod microfacet;
+pub mod onb;
+pub mod pcg;
+pub mod quad;
+pub mod ray;
+pub mod sampler;
+pub mod spectrum;
+pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod utils;
+pub mod vec3;
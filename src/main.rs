@@ -1,22 +1,23 @@
 use std::sync::Arc;
 
-use camera::Camera;
-use hittable::HittableList;
-use materials::{Dielectric, Lambertian, Metal};
 use rand::Rng;
-use sphere::Sphere;
-use vec3::Vector3;
-
-mod camera;
-mod color;
-mod hittable;
-mod materials;
-mod ray;
-mod sphere;
-mod utils;
-mod vec3;
+use raytracer::{
+    camera::Camera,
+    hittable::HittableList,
+    materials::{Dielectric, Lambertian, Metal},
+    pcg::Pcg32,
+    sphere::Sphere,
+    vec3::Vector3,
+};
 
 fn main() {
+    // The seed picks both the scene and the sampling noise, so the same seed renders the same
+    // image every time.
+    let seed = match std::env::args().nth(1) {
+        Some(arg) => arg.parse().expect("seed must be an unsigned integer"),
+        None => 0,
+    };
+
     let mut world = HittableList::default();
 
     let ground_material = Arc::new(Lambertian::new(Vector3::new(0.5, 0.5, 0.5)));
@@ -26,7 +27,7 @@ fn main() {
         ground_material,
     ));
 
-    let mut rng = rand::thread_rng();
+    let mut rng = Pcg32::new(seed, 0);
     let mut a = -11.0;
     while a < 11.0 {
         let mut b = -11.0;
@@ -36,11 +37,11 @@ fn main() {
 
             if (center - Vector3::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 if choose_mat < 0.8 {
-                    let albedo = Vector3::random(0.0, 1.0);
+                    let albedo = Vector3::random(&mut rng, 0.0, 1.0);
                     let sphere_material = Arc::new(Lambertian::new(albedo));
                     world.push(Sphere::new(center, 0.2, sphere_material));
                 } else if choose_mat < 0.95 {
-                    let albedo = Vector3::random(0.5, 1.0);
+                    let albedo = Vector3::random(&mut rng, 0.5, 1.0);
                     let fuzz = rng.gen_range(0.0..0.5);
                     let sphere_material = Arc::new(Metal::new(albedo, fuzz));
                     world.push(Sphere::new(center, 0.2, sphere_material));
@@ -64,7 +65,7 @@ fn main() {
     let material3 = Arc::new(Metal::new(Vector3::new(0.7, 0.6, 0.5), 0.0));
     world.push(Sphere::new(Vector3::new(4.0, 1.0, 0.0), 1.0, material3));
 
-    let camera = Camera::new(
+    let mut camera = Camera::new(
         400,
         16.0 / 9.0,
         100,
@@ -76,5 +77,6 @@ fn main() {
         0.6,
         10.0,
     );
-    camera.render(world);
+    camera.set_seed(seed);
+    camera.render(&world);
 }
@@ -1,9 +1,45 @@
-use rand::Rng;
+use std::sync::Arc;
 
-use crate::{hittable::HitRecord, ray::Ray, vec3::Vector3};
+use crate::{
+    hittable::HitRecord,
+    microfacet::{
+        fresnel_conductor, fresnel_conductor_rgb, fresnel_dielectric, thin_film_reflectance,
+        TrowbridgeReitz,
+    },
+    onb::Onb,
+    ray::Ray,
+    sampler::{sample_unit_sphere, Sampler},
+    spectrum::{rgb_at, RefractiveIndex, LAMBDA_REFERENCE, RGB_WAVELENGTHS},
+    texture::{SolidColor, Texture},
+    utils::degrees_to_radians,
+    vec3::Vector3,
+};
 
+// The attenuation returned by `scatter` is expressed in the same terms as the radiance carried by
+// `r_in`: RGB, or values at the ray's wavelengths in spectral mode (see `Ray::attenuation`). Random
+// choices draw their values from `sampler`.
 pub trait Scatterable {
-    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Vector3, Ray)>;
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)>;
+
+    // Interface of the surface at `lambda` nanometers, for materials bounded by a specular
+    // interface. Wrappers such as thin-film coatings use it to work out the reflectance of what
+    // lies beneath them.
+    fn interface_ior(&self, _lambda: f64) -> Option<Interface> {
+        None
+    }
+}
+
+// Specular interface bounding a material, with its complex index of refraction.
+#[derive(Clone, Copy)]
+pub struct Interface {
+    pub eta: f64,     // Real part of the index of refraction
+    pub k: f64,       // Absorption coefficient (imaginary part of the index)
+    pub opaque: bool, // Light is only reflected, never refracted into the material
 }
 
 pub struct Lambertian {
@@ -17,14 +53,80 @@ impl Lambertian {
 }
 
 impl Scatterable for Lambertian {
-    fn scatter(&self, _r_in: &Ray, hit_record: &HitRecord) -> Option<(Vector3, Ray)> {
-        let mut scatter_direction = hit_record.normal + Vector3::random_unit_vector();
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        let mut scatter_direction = hit_record.normal + sample_unit_sphere(sampler.get_2d());
         if scatter_direction.near_zero() {
             scatter_direction = hit_record.normal;
         }
 
-        let scattered_ray = Ray::new(hit_record.p, scatter_direction);
-        Some((self.albedo, scattered_ray))
+        let scattered_ray = r_in.spawn(hit_record.p, scatter_direction);
+        Some((r_in.attenuation(self.albedo), scattered_ray))
+    }
+}
+
+// Rough diffuse surface made of V-shaped Lambertian microfacets (Oren and Nayar 1994), using
+// the qualitative model. Unlike `Lambertian` it gets flatter and brighter towards the light,
+// which suits clay, concrete and fabric. A `sigma` of zero matches `Lambertian`.
+pub struct OrenNayar {
+    albedo: Vector3,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    // `sigma` is the standard deviation of the microfacet slope angle, in degrees.
+    pub fn new(albedo: Vector3, sigma: f64) -> OrenNayar {
+        let sigma = degrees_to_radians(sigma);
+        let sigma2 = sigma * sigma;
+        OrenNayar {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+}
+
+impl Scatterable for OrenNayar {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        // Cosine-weighted sampling as in `Lambertian`, so the cosine and pdf cancel out.
+        let mut scatter_direction = hit_record.normal + sample_unit_sphere(sampler.get_2d());
+        if scatter_direction.near_zero() {
+            scatter_direction = hit_record.normal;
+        }
+
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(-r_in.direction.unit_vector());
+        let wi = frame.to_local(scatter_direction.unit_vector());
+
+        let sin_theta_o = (1.0 - wo[2] * wo[2]).max(0.0).sqrt();
+        let sin_theta_i = (1.0 - wi[2] * wi[2]).max(0.0).sqrt();
+
+        // Cosine of the azimuthal angle between the two directions.
+        let max_cos = if sin_theta_o > 1e-4 && sin_theta_i > 1e-4 {
+            ((wo[0] * wi[0] + wo[1] * wi[1]) / (sin_theta_o * sin_theta_i)).max(0.0)
+        } else {
+            0.0
+        };
+
+        let (sin_alpha, tan_beta) = if wi[2].abs() > wo[2].abs() {
+            (sin_theta_o, sin_theta_i / wi[2].abs())
+        } else {
+            (sin_theta_i, sin_theta_o / wo[2].abs().max(1e-8))
+        };
+
+        let attenuation = (self.a + self.b * max_cos * sin_alpha * tan_beta) * self.albedo;
+        let scattered_ray = r_in.spawn(hit_record.p, scatter_direction);
+        Some((r_in.attenuation(attenuation), scattered_ray))
     }
 }
 
@@ -43,25 +145,216 @@ impl Metal {
 }
 
 impl Scatterable for Metal {
-    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Vector3, Ray)> {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
         let reflection = Vector3::reflect(r_in.direction, hit_record.normal)
-            + (self.fuzz * Vector3::random_unit_vector());
-        let scattered_ray = Ray::new(hit_record.p, reflection);
+            + (self.fuzz * sample_unit_sphere(sampler.get_2d()));
+        let scattered_ray = r_in.spawn(hit_record.p, reflection);
 
         if scattered_ray.direction.dot(hit_record.normal) > 0.0 {
-            Some((self.albedo, scattered_ray))
+            Some((r_in.attenuation(self.albedo), scattered_ray))
         } else {
             None
         }
     }
+
+    fn interface_ior(&self, lambda: f64) -> Option<Interface> {
+        // Treat the albedo as the reflectance at normal incidence of a non-absorbing interface,
+        // which only reflects.
+        let f0 = rgb_at(self.albedo, lambda).clamp(0.0, 0.999).sqrt();
+        Some(Interface {
+            eta: (1.0 + f0) / (1.0 - f0),
+            k: 0.0,
+            opaque: true,
+        })
+    }
+}
+
+// Rough metal modelled with a GGX microfacet distribution and the exact Fresnel equations for a
+// complex index of refraction, so that color shifts towards grazing angles come out right.
+pub struct Conductor {
+    eta: Vector3, // Real part of the index of refraction, per RGB channel
+    k: Vector3,   // Absorption coefficient (imaginary part of the index), per RGB channel
+    distribution: TrowbridgeReitz,
+}
+
+impl Conductor {
+    pub fn new(eta: Vector3, k: Vector3, roughness_u: f64, roughness_v: f64) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::new(
+                TrowbridgeReitz::roughness_to_alpha(roughness_u),
+                TrowbridgeReitz::roughness_to_alpha(roughness_v),
+            ),
+        }
+    }
+
+    pub fn gold(roughness: f64) -> Conductor {
+        Conductor::new(
+            Vector3::new(0.143, 0.374, 1.442),
+            Vector3::new(3.983, 2.385, 1.603),
+            roughness,
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Conductor {
+        Conductor::new(
+            Vector3::new(0.155, 0.117, 0.138),
+            Vector3::new(4.828, 3.122, 2.147),
+            roughness,
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Conductor {
+        Conductor::new(
+            Vector3::new(0.200, 0.924, 1.102),
+            Vector3::new(3.912, 2.452, 2.142),
+            roughness,
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Conductor {
+        Conductor::new(
+            Vector3::new(1.657, 0.880, 0.521),
+            Vector3::new(9.224, 6.270, 4.837),
+            roughness,
+            roughness,
+        )
+    }
+
+    pub fn iron(roughness: f64) -> Conductor {
+        Conductor::new(
+            Vector3::new(2.911, 2.950, 2.585),
+            Vector3::new(3.089, 2.932, 2.767),
+            roughness,
+            roughness,
+        )
+    }
+}
+
+impl Conductor {
+    fn fresnel(&self, r_in: &Ray, cos_theta: f64) -> Vector3 {
+        match r_in.wavelengths {
+            Some(wavelengths) => {
+                let [l0, l1, l2] = wavelengths.lambdas().map(|lambda| {
+                    let interface = self.interface_ior(lambda).unwrap();
+                    fresnel_conductor(cos_theta, interface.eta, interface.k)
+                });
+                Vector3::new(l0, l1, l2)
+            }
+            None => fresnel_conductor_rgb(cos_theta, self.eta, self.k),
+        }
+    }
 }
+
+impl Scatterable for Conductor {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(-r_in.direction.unit_vector());
+        if wo[2] <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.effectively_smooth() {
+            let wi = Vector3::new(-wo[0], -wo[1], wo[2]);
+            let attenuation = self.fresnel(r_in, wo[2]);
+            return Some((attenuation, r_in.spawn(hit_record.p, frame.to_world(wi))));
+        }
+
+        // Sample a visible microfacet normal and reflect about it. With this sampling strategy
+        // D and the cosine terms cancel out, leaving F * G / G1 as the path weight.
+        let wm = self.distribution.sample_wm(wo, sampler.get_2d());
+        let wi = Vector3::reflect(-wo, wm);
+        if wi[2] <= 0.0 {
+            return None;
+        }
+
+        let fresnel = self.fresnel(r_in, wo.dot(wm));
+        let masking = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+        let attenuation = masking * fresnel;
+
+        Some((attenuation, r_in.spawn(hit_record.p, frame.to_world(wi))))
+    }
+
+    fn interface_ior(&self, lambda: f64) -> Option<Interface> {
+        Some(Interface {
+            eta: rgb_at(self.eta, lambda),
+            k: rgb_at(self.k, lambda),
+            opaque: true,
+        })
+    }
+}
+
 pub struct Dielectric {
-    refraction_index: f64,
+    refraction_index: RefractiveIndex,
+    absorption: Vector3, // Beer-Lambert absorption coefficient per unit of distance
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f64) -> Dielectric {
-        Dielectric { refraction_index }
+        Dielectric::with_absorption(refraction_index, Vector3::new(0.0, 0.0, 0.0))
+    }
+
+    pub fn with_absorption(refraction_index: f64, absorption: Vector3) -> Dielectric {
+        Dielectric {
+            refraction_index: RefractiveIndex::Constant(refraction_index),
+            absorption,
+        }
+    }
+
+    // Glass whose index of refraction varies with wavelength. Dispersion only shows up in
+    // spectral mode; RGB renders use the index at the reference wavelength.
+    pub fn dispersive(refraction_index: RefractiveIndex) -> Dielectric {
+        Dielectric {
+            refraction_index,
+            absorption: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // Colored glass described by the fraction of light that is left after travelling `distance`
+    // through the medium, which must be positive.
+    pub fn tinted(refraction_index: f64, transmission_color: Vector3, distance: f64) -> Dielectric {
+        assert!(
+            distance > 0.0,
+            "tinted glass needs a positive reference distance"
+        );
+        let coefficient = |c: f64| -c.max(1e-6).ln() / distance;
+        Dielectric::with_absorption(
+            refraction_index,
+            Vector3::new(
+                coefficient(transmission_color[0]),
+                coefficient(transmission_color[1]),
+                coefficient(transmission_color[2]),
+            ),
+        )
+    }
+
+    // Fraction of light surviving the path inside the medium. A ray hitting the back face has
+    // been travelling through the interior since it entered or last reflected internally.
+    fn transmittance(&self, r_in: &Ray, hit_record: &HitRecord) -> Vector3 {
+        if hit_record.front_face {
+            return Vector3::new(1.0, 1.0, 1.0);
+        }
+
+        let distance = hit_record.t * r_in.direction.length();
+        Vector3::new(
+            (-self.absorption[0] * distance).exp(),
+            (-self.absorption[1] * distance).exp(),
+            (-self.absorption[2] * distance).exp(),
+        )
     }
 
     fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
@@ -72,12 +365,30 @@ impl Dielectric {
 }
 
 impl Scatterable for Dielectric {
-    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Vector3, Ray)> {
-        let albedo = Vector3::new(1.0, 1.0, 1.0);
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        let mut albedo = r_in.attenuation(self.transmittance(r_in, hit_record));
+
+        // A dispersive interface sends every wavelength in a different direction, so only the
+        // hero wavelength can follow the refracted ray.
+        let (index, wavelengths) = match r_in.wavelengths {
+            Some(wavelengths) if self.refraction_index.is_dispersive() => {
+                albedo = albedo * wavelengths.termination_weight();
+                (
+                    self.refraction_index.at(wavelengths.hero()),
+                    Some(wavelengths.terminate_secondary()),
+                )
+            }
+            wavelengths => (self.refraction_index.at(LAMBDA_REFERENCE), wavelengths),
+        };
         let refraction_index = if hit_record.front_face {
-            1.0 / self.refraction_index
+            1.0 / index
         } else {
-            self.refraction_index
+            index
         };
 
         let unit_direction = r_in.direction.unit_vector();
@@ -86,16 +397,998 @@ impl Scatterable for Dielectric {
 
         let cannot_refract = refraction_index * sin_theta > 1.0;
         let direction = if cannot_refract
-            || Dielectric::reflectance(cos_theta, refraction_index)
-                > rand::thread_rng().gen_range(0.0..1.0)
+            || Dielectric::reflectance(cos_theta, refraction_index) > sampler.get_1d()
         {
             Vector3::reflect(unit_direction, hit_record.normal)
         } else {
             Vector3::refract(unit_direction, hit_record.normal, refraction_index)
         };
 
-        let scattered_ray = Ray::new(hit_record.p, direction);
+        let mut scattered_ray = r_in.spawn(hit_record.p, direction);
+        scattered_ray.wavelengths = wavelengths;
 
         Some((albedo, scattered_ray))
     }
+
+    fn interface_ior(&self, lambda: f64) -> Option<Interface> {
+        Some(Interface {
+            eta: self.refraction_index.at(lambda),
+            k: 0.0,
+            opaque: false,
+        })
+    }
+}
+
+// Frosted glass: a dielectric interface whose microfacets follow a GGX distribution. Each scatter
+// picks a visible microfacet, then reflects or refracts through it with the exact Fresnel
+// reflectance as the selection probability.
+pub struct RoughDielectric {
+    refraction_index: f64,
+    distribution: TrowbridgeReitz,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64) -> RoughDielectric {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        RoughDielectric {
+            refraction_index,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
+        }
+    }
+}
+
+impl Scatterable for RoughDielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        let albedo = Vector3::new(1.0, 1.0, 1.0);
+        // Ratio of the index of refraction past the surface over the one the ray travels in.
+        let eta = if hit_record.front_face {
+            self.refraction_index
+        } else {
+            1.0 / self.refraction_index
+        };
+
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(-r_in.direction.unit_vector());
+        if wo[2] <= 0.0 {
+            return None;
+        }
+        let wm = if self.distribution.effectively_smooth() {
+            Vector3::new(0.0, 0.0, 1.0)
+        } else {
+            self.distribution.sample_wm(wo, sampler.get_2d())
+        };
+
+        let cos_theta = wo.dot(wm);
+        let reflectance = fresnel_dielectric(cos_theta, eta);
+        let wi = if sampler.get_1d() < reflectance {
+            let wi = Vector3::reflect(-wo, wm);
+            if wi[2] <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let wi = Vector3::refract(-wo, wm, 1.0 / eta);
+            if wi[2] >= 0.0 {
+                return None;
+            }
+            wi
+        };
+
+        // Fresnel terms cancel with the selection probabilities and, with visible normal sampling,
+        // D and the cosines cancel as well; only the Smith G / G1 ratio remains.
+        let attenuation = if self.distribution.effectively_smooth() {
+            albedo
+        } else {
+            (self.distribution.g(wo, wi) / self.distribution.g1(wo)) * albedo
+        };
+
+        Some((
+            r_in.attenuation(attenuation),
+            r_in.spawn(hit_record.p, frame.to_world(wi)),
+        ))
+    }
+
+    fn interface_ior(&self, _lambda: f64) -> Option<Interface> {
+        Some(Interface {
+            eta: self.refraction_index,
+            k: 0.0,
+            opaque: false,
+        })
+    }
+}
+
+// Principled BSDF after Burley's "Physically Based Shading at Disney". A single material blends a
+// diffuse base with sheen, a GGX specular layer, rough glass transmission and a clearcoat. Every
+// scatter picks one of these lobes with a probability based on its expected contribution and
+// divides the lobe's weight by that probability.
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    metallic: Arc<dyn Texture>,
+    roughness: Arc<dyn Texture>,
+    specular: Arc<dyn Texture>,
+    specular_tint: Arc<dyn Texture>,
+    sheen: Arc<dyn Texture>,
+    clearcoat: Arc<dyn Texture>,
+    transmission: Arc<dyn Texture>,
+    refraction_index: Arc<dyn Texture>,
+}
+
+impl Principled {
+    const SHEEN_TINT: f64 = 0.5;
+    const CLEARCOAT_ALPHA: f64 = 0.01;
+
+    pub fn new(base_color: Arc<dyn Texture>) -> Principled {
+        Principled {
+            base_color,
+            metallic: Arc::new(SolidColor::scalar(0.0)),
+            roughness: Arc::new(SolidColor::scalar(0.5)),
+            specular: Arc::new(SolidColor::scalar(0.5)),
+            specular_tint: Arc::new(SolidColor::scalar(0.0)),
+            sheen: Arc::new(SolidColor::scalar(0.0)),
+            clearcoat: Arc::new(SolidColor::scalar(0.0)),
+            transmission: Arc::new(SolidColor::scalar(0.0)),
+            refraction_index: Arc::new(SolidColor::scalar(1.5)),
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: Arc<dyn Texture>) -> Principled {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: Arc<dyn Texture>) -> Principled {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn with_specular(mut self, specular: Arc<dyn Texture>) -> Principled {
+        self.specular = specular;
+        self
+    }
+
+    pub fn with_specular_tint(mut self, specular_tint: Arc<dyn Texture>) -> Principled {
+        self.specular_tint = specular_tint;
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: Arc<dyn Texture>) -> Principled {
+        self.sheen = sheen;
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: Arc<dyn Texture>) -> Principled {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: Arc<dyn Texture>) -> Principled {
+        self.transmission = transmission;
+        self
+    }
+
+    pub fn with_refraction_index(mut self, refraction_index: Arc<dyn Texture>) -> Principled {
+        self.refraction_index = refraction_index;
+        self
+    }
+
+    fn schlick_weight(cosine: f64) -> f64 {
+        (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+    }
+
+    fn luminance(color: Vector3) -> f64 {
+        0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]
+    }
+
+    // Reflects `wo` about a visible normal of `distribution`, returning the direction along with
+    // the G / G1 weight left over after visible normal sampling.
+    fn sample_reflection(
+        distribution: &TrowbridgeReitz,
+        wo: Vector3,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Vector3, f64)> {
+        if distribution.effectively_smooth() {
+            let wm = Vector3::new(0.0, 0.0, 1.0);
+            return Some((Vector3::reflect(-wo, wm), wm, 1.0));
+        }
+
+        let wm = distribution.sample_wm(wo, sampler.get_2d());
+        let wi = Vector3::reflect(-wo, wm);
+        if wi[2] <= 0.0 {
+            return None;
+        }
+
+        Some((wi, wm, distribution.g(wo, wi) / distribution.g1(wo)))
+    }
+
+    // Rough glass lobe, reflecting or refracting through a visible microfacet normal. Refracted
+    // light is tinted on the way in only, so that a closed object tints it once.
+    fn sample_transmission(
+        distribution: &TrowbridgeReitz,
+        wo: Vector3,
+        refraction_index: f64,
+        front_face: bool,
+        tint: Vector3,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Vector3)> {
+        let (eta, tint) = if front_face {
+            (refraction_index, tint)
+        } else {
+            (1.0 / refraction_index, Vector3::new(1.0, 1.0, 1.0))
+        };
+
+        let wm = if distribution.effectively_smooth() {
+            Vector3::new(0.0, 0.0, 1.0)
+        } else {
+            distribution.sample_wm(wo, sampler.get_2d())
+        };
+
+        let reflected = sampler.get_1d() < fresnel_dielectric(wo.dot(wm), eta);
+        let (wi, color) = if reflected {
+            (Vector3::reflect(-wo, wm), Vector3::new(1.0, 1.0, 1.0))
+        } else {
+            (Vector3::refract(-wo, wm, 1.0 / eta), tint)
+        };
+        if reflected != (wi[2] > 0.0) {
+            return None;
+        }
+
+        if distribution.effectively_smooth() {
+            Some((wi, color))
+        } else {
+            Some((wi, (distribution.g(wo, wi) / distribution.g1(wo)) * color))
+        }
+    }
+}
+
+impl Scatterable for Principled {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        let (u, v, p) = (hit_record.u, hit_record.v, hit_record.p);
+        let base_color = self.base_color.value(u, v, p);
+        let metallic = self.metallic.value(u, v, p)[0].clamp(0.0, 1.0);
+        let roughness = self.roughness.value(u, v, p)[0].clamp(0.0, 1.0);
+        let specular = self.specular.value(u, v, p)[0].max(0.0);
+        let specular_tint = self.specular_tint.value(u, v, p)[0].clamp(0.0, 1.0);
+        let sheen = self.sheen.value(u, v, p)[0].max(0.0);
+        let clearcoat = self.clearcoat.value(u, v, p)[0].max(0.0);
+        let transmission = self.transmission.value(u, v, p)[0].clamp(0.0, 1.0);
+        let refraction_index = self.refraction_index.value(u, v, p)[0].max(1.0);
+
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(-r_in.direction.unit_vector());
+        if wo[2] <= 0.0 {
+            return None;
+        }
+
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness).max(1e-4);
+        let distribution = TrowbridgeReitz::new(alpha, alpha);
+
+        // Rays travelling inside the material can only have entered through the glass lobe.
+        if !hit_record.front_face {
+            let (wi, attenuation) = Principled::sample_transmission(
+                &distribution,
+                wo,
+                refraction_index,
+                false,
+                base_color,
+                sampler,
+            )?;
+            return Some((
+                r_in.attenuation(attenuation),
+                r_in.spawn(p, frame.to_world(wi)),
+            ));
+        }
+
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        let luminance = Principled::luminance(base_color);
+        let tint = if luminance > 0.0 {
+            base_color / luminance
+        } else {
+            white
+        };
+        let specular_color = (1.0 - metallic)
+            * (0.08 * specular * ((1.0 - specular_tint) * white + specular_tint * tint))
+            + metallic * base_color;
+
+        let diffuse_weight = (1.0 - metallic) * (1.0 - transmission);
+        let specular_weight = 1.0 - (1.0 - metallic) * transmission;
+        let transmission_weight = (1.0 - metallic) * transmission;
+        let clearcoat_weight = 0.25 * clearcoat;
+
+        // Pick a lobe proportionally to a cheap estimate of how much light it reflects.
+        let fresnel_o = Principled::schlick_weight(wo[2]);
+        let pdfs = [
+            diffuse_weight * (luminance + sheen).max(1e-3),
+            specular_weight
+                * Principled::luminance(specular_color + fresnel_o * (white - specular_color))
+                    .max(1e-3),
+            transmission_weight,
+            clearcoat_weight * (0.04 + 0.96 * fresnel_o),
+        ];
+        let total: f64 = pdfs.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut pick = sampler.get_1d() * total;
+        let mut lobe = pdfs.len() - 1;
+        for (index, pdf) in pdfs.iter().enumerate() {
+            if pick < *pdf {
+                lobe = index;
+                break;
+            }
+            pick -= pdf;
+        }
+        let lobe_probability = pdfs[lobe] / total;
+
+        let (wi, attenuation) = match lobe {
+            0 => {
+                // Burley diffuse with retro-reflection and a sheen term, cosine-sampled so that
+                // the cosine and pdf cancel.
+                let mut direction =
+                    Vector3::new(0.0, 0.0, 1.0) + sample_unit_sphere(sampler.get_2d());
+                if direction.near_zero() {
+                    direction = Vector3::new(0.0, 0.0, 1.0);
+                }
+                let wi = direction.unit_vector();
+                let wh = (wi + wo).unit_vector();
+                let cos_theta_d = wi.dot(wh);
+
+                let fd90 = 0.5 + 2.0 * roughness * cos_theta_d * cos_theta_d;
+                let fd = (1.0 + (fd90 - 1.0) * Principled::schlick_weight(wi[2]))
+                    * (1.0 + (fd90 - 1.0) * Principled::schlick_weight(wo[2]));
+                let sheen_color =
+                    (1.0 - Principled::SHEEN_TINT) * white + Principled::SHEEN_TINT * tint;
+                let sheen_term =
+                    (sheen * Principled::schlick_weight(cos_theta_d) * std::f64::consts::PI)
+                        * sheen_color;
+
+                (wi, diffuse_weight * (fd * base_color + sheen_term))
+            }
+            1 => {
+                let (wi, wm, masking) = Principled::sample_reflection(&distribution, wo, sampler)?;
+                let fresnel_h = Principled::schlick_weight(wo.dot(wm));
+                let fresnel = specular_color + fresnel_h * (white - specular_color);
+                (wi, (specular_weight * masking) * fresnel)
+            }
+            2 => {
+                let (wi, attenuation) = Principled::sample_transmission(
+                    &distribution,
+                    wo,
+                    refraction_index,
+                    true,
+                    base_color,
+                    sampler,
+                )?;
+                (wi, transmission_weight * attenuation)
+            }
+            _ => {
+                let distribution =
+                    TrowbridgeReitz::new(Principled::CLEARCOAT_ALPHA, Principled::CLEARCOAT_ALPHA);
+                let (wi, wm, masking) = Principled::sample_reflection(&distribution, wo, sampler)?;
+                let fresnel = 0.04 + 0.96 * Principled::schlick_weight(wo.dot(wm));
+                (wi, (clearcoat_weight * masking * fresnel) * white)
+            }
+        };
+
+        Some((
+            r_in.attenuation(attenuation / lobe_probability),
+            r_in.spawn(p, frame.to_world(wi)),
+        ))
+    }
+}
+
+// Iridescent coating over a material with a specular interface, such as `Dielectric`, `Metal` or
+// `Conductor`. On transparent bases the film replaces the outer interface: light is reflected or
+// refracted with the reflectance of the coated surface, and the base only handles the interior.
+// On opaque bases the reflections of the base are reweighted from the reflectance of the bare
+// interface to that of the coated one.
+pub struct ThinFilm {
+    base: Arc<dyn Scatterable>,
+    thickness: f64,        // Film thickness in nanometers
+    refraction_index: f64, // Index of refraction of the film
+}
+
+impl ThinFilm {
+    pub fn new(base: Arc<dyn Scatterable>, thickness: f64, refraction_index: f64) -> ThinFilm {
+        ThinFilm {
+            base,
+            thickness,
+            refraction_index,
+        }
+    }
+}
+
+impl Scatterable for ThinFilm {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        // The film coats the outside of the object.
+        if !hit_record.front_face {
+            return self.base.scatter(r_in, hit_record, sampler);
+        }
+
+        let lambdas = match r_in.wavelengths {
+            Some(wavelengths) => wavelengths.lambdas(),
+            None => RGB_WAVELENGTHS,
+        };
+        let mut interfaces = [Interface {
+            eta: 1.0,
+            k: 0.0,
+            opaque: false,
+        }; 3];
+        for (interface, lambda) in interfaces.iter_mut().zip(lambdas) {
+            match self.base.interface_ior(lambda) {
+                Some(base_interface) => *interface = base_interface,
+                None => return self.base.scatter(r_in, hit_record, sampler),
+            }
+        }
+
+        let unit_direction = r_in.direction.unit_vector();
+        let cos_theta = (-unit_direction).dot(hit_record.normal).min(1.0);
+        let coated = |i: usize| {
+            thin_film_reflectance(
+                cos_theta,
+                lambdas[i],
+                self.thickness,
+                self.refraction_index,
+                interfaces[i].eta,
+                interfaces[i].k,
+            )
+        };
+        let coated = Vector3::new(coated(0), coated(1), coated(2));
+
+        if interfaces[0].opaque {
+            let (attenuation, scattered) = self.base.scatter(r_in, hit_record, sampler)?;
+            let bare = |i: usize| {
+                fresnel_conductor(cos_theta, interfaces[i].eta, interfaces[i].k).max(1e-6)
+            };
+            let weight = coated / Vector3::new(bare(0), bare(1), bare(2));
+            return Some((weight * attenuation, scattered));
+        }
+
+        let reflect_probability = ((coated[0] + coated[1] + coated[2]) / 3.0).clamp(1e-3, 1.0);
+        let refraction_index = match r_in.wavelengths {
+            Some(wavelengths) => self.base.interface_ior(wavelengths.hero())?.eta,
+            None => self.base.interface_ior(LAMBDA_REFERENCE)?.eta,
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let cannot_refract = sin_theta / refraction_index > 1.0;
+
+        if cannot_refract || sampler.get_1d() < reflect_probability {
+            let direction = Vector3::reflect(unit_direction, hit_record.normal);
+            let weight = if cannot_refract {
+                Vector3::new(1.0, 1.0, 1.0)
+            } else {
+                coated / reflect_probability
+            };
+            return Some((weight, r_in.spawn(hit_record.p, direction)));
+        }
+
+        let direction = Vector3::refract(unit_direction, hit_record.normal, 1.0 / refraction_index);
+        let mut scattered = r_in.spawn(hit_record.p, direction);
+        let mut weight =
+            (Vector3::new(1.0, 1.0, 1.0) - coated) / (1.0 - reflect_probability).max(1e-6);
+
+        // As in `Dielectric`, only the hero wavelength survives a dispersive refraction.
+        if let Some(wavelengths) = r_in.wavelengths {
+            if interfaces.iter().any(|i| i.eta != interfaces[0].eta) {
+                weight = weight * wavelengths.termination_weight();
+                scattered.wavelengths = Some(wavelengths.terminate_secondary());
+            }
+        }
+
+        Some((weight, scattered))
+    }
+
+    fn interface_ior(&self, lambda: f64) -> Option<Interface> {
+        self.base.interface_ior(lambda)
+    }
+}
+
+// Blend of two materials. Each scatter picks `second` with a probability given by the first
+// channel of `weight`, so on average the result is the weighted sum of both materials.
+pub struct Mix {
+    first: Arc<dyn Scatterable>,
+    second: Arc<dyn Scatterable>,
+    weight: Arc<dyn Texture>,
+}
+
+impl Mix {
+    pub fn new(
+        first: Arc<dyn Scatterable>,
+        second: Arc<dyn Scatterable>,
+        weight: Arc<dyn Texture>,
+    ) -> Mix {
+        Mix {
+            first,
+            second,
+            weight,
+        }
+    }
+}
+
+impl Scatterable for Mix {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        let weight = self.weight.value(hit_record.u, hit_record.v, hit_record.p)[0].clamp(0.0, 1.0);
+
+        if sampler.get_1d() < weight {
+            self.second.scatter(r_in, hit_record, sampler)
+        } else {
+            self.first.scatter(r_in, hit_record, sampler)
+        }
+    }
+}
+
+// Material whose parameters change over time. `build` makes the material for a given time,
+// usually from `Keyframes` of its parameters, and each scatter uses the material at the time of
+// the incoming ray. A roughness keyed from 0.1 to 0.9 is rendered with a roughness of 0.5
+// halfway between the keys, rather than as a blend of the two ends.
+pub struct Animated<F> {
+    build: F,
+}
+
+impl<M, F> Animated<F>
+where
+    M: Scatterable,
+    F: Fn(f64) -> M,
+{
+    pub fn new(build: F) -> Animated<F> {
+        Animated { build }
+    }
+}
+
+impl<M, F> Scatterable for Animated<F>
+where
+    M: Scatterable,
+    F: Fn(f64) -> M,
+{
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        (self.build)(r_in.time).scatter(r_in, hit_record, sampler)
+    }
+}
+
+// Clear dielectric layer over any base material, like varnish over wood. Light is either
+// reflected by the coat, with the Fresnel reflectance of its (possibly rough) interface as the
+// selection probability, or goes through to the base and is attenuated by the coat's
+// transmittance on its way back out.
+pub struct Coated {
+    base: Arc<dyn Scatterable>,
+    refraction_index: f64,
+    distribution: TrowbridgeReitz,
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Scatterable>, refraction_index: f64, roughness: f64) -> Coated {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        Coated {
+            base,
+            refraction_index,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
+        }
+    }
+}
+
+impl Scatterable for Coated {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        if !hit_record.front_face {
+            return self.base.scatter(r_in, hit_record, sampler);
+        }
+
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(-r_in.direction.unit_vector());
+        if wo[2] <= 0.0 {
+            return None;
+        }
+        let wm = if self.distribution.effectively_smooth() {
+            Vector3::new(0.0, 0.0, 1.0)
+        } else {
+            self.distribution.sample_wm(wo, sampler.get_2d())
+        };
+
+        if sampler.get_1d() < fresnel_dielectric(wo.dot(wm), self.refraction_index) {
+            let wi = Vector3::reflect(-wo, wm);
+            if wi[2] <= 0.0 {
+                return None;
+            }
+            let masking = if self.distribution.effectively_smooth() {
+                1.0
+            } else {
+                self.distribution.g(wo, wi) / self.distribution.g1(wo)
+            };
+            let attenuation = r_in.attenuation(Vector3::new(masking, masking, masking));
+            return Some((attenuation, r_in.spawn(hit_record.p, frame.to_world(wi))));
+        }
+
+        // The probability of entering the coat cancels its transmittance on the way in.
+        let (attenuation, scattered) = self.base.scatter(r_in, hit_record, sampler)?;
+        let cos_theta_out = scattered.direction.unit_vector().dot(hit_record.normal);
+        let transmittance = 1.0 - fresnel_dielectric(cos_theta_out, self.refraction_index);
+
+        Some((transmittance * attenuation, scattered))
+    }
+
+    fn interface_ior(&self, lambda: f64) -> Option<Interface> {
+        // Light gets through the coat, so the base decides whether it goes any further.
+        let base = self.base.interface_ior(lambda);
+        Some(Interface {
+            eta: self.refraction_index,
+            k: 0.0,
+            opaque: base.is_none_or(|base| base.opaque),
+        })
+    }
+}
+
+// Perturbs the shading normal with a tangent-space normal map before handing the hit to `base`.
+// The map is read as RGB in [0, 1] encoding a normal whose Z axis follows the surface normal, so
+// it should be a linear `ImageTexture`.
+pub struct NormalMapped {
+    base: Arc<dyn Scatterable>,
+    normal_map: Arc<dyn Texture>,
+}
+
+impl NormalMapped {
+    pub fn new(base: Arc<dyn Scatterable>, normal_map: Arc<dyn Texture>) -> NormalMapped {
+        NormalMapped { base, normal_map }
+    }
+}
+
+impl Scatterable for NormalMapped {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        if hit_record.dpdu.near_zero() {
+            return self.base.scatter(r_in, hit_record, sampler);
+        }
+
+        let encoded = self
+            .normal_map
+            .value(hit_record.u, hit_record.v, hit_record.p);
+        let local = 2.0 * encoded - Vector3::new(1.0, 1.0, 1.0);
+
+        // Tangent frame around the outward normal, with the bitangent following dpdv.
+        let normal = hit_record.outward_normal();
+        let tangent = (hit_record.dpdu - hit_record.dpdu.dot(normal) * normal).unit_vector();
+        let mut bitangent = normal.cross(tangent);
+        if bitangent.dot(hit_record.dpdv) < 0.0 {
+            bitangent = -bitangent;
+        }
+
+        let mapped = (local[0] * tangent + local[1] * bitangent + local[2] * normal).unit_vector();
+        let mut shading = hit_record.clone();
+        shading.normal = if hit_record.front_face {
+            mapped
+        } else {
+            -mapped
+        };
+
+        self.base.scatter(r_in, &shading, sampler)
+    }
+
+    fn interface_ior(&self, lambda: f64) -> Option<Interface> {
+        self.base.interface_ior(lambda)
+    }
+}
+
+// Perturbs the shading normal as if the surface were displaced along its normal by the first
+// channel of `height`, scaled by `scale`, before handing the hit to `base`.
+pub struct BumpMapped {
+    base: Arc<dyn Scatterable>,
+    height: Arc<dyn Texture>,
+    scale: f64,
+}
+
+impl BumpMapped {
+    pub fn new(base: Arc<dyn Scatterable>, height: Arc<dyn Texture>, scale: f64) -> BumpMapped {
+        BumpMapped {
+            base,
+            height,
+            scale,
+        }
+    }
+}
+
+impl Scatterable for BumpMapped {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        if hit_record.dpdu.near_zero() || hit_record.dpdv.near_zero() {
+            return self.base.scatter(r_in, hit_record, sampler);
+        }
+
+        // Estimate the height derivatives with finite differences in texture space. Image lookups
+        // are piecewise constant, so a small step would mostly land in the same texel; there the
+        // difference spans one texel instead, moved inwards at the edges where lookups clamp.
+        let (u, v, p) = (hit_record.u, hit_record.v, hit_record.p);
+        let (u0, u1, v0, v1) = match self.height.texel_size() {
+            Some((du, dv)) => {
+                let u = u.max(du).min(1.0 - du);
+                let v = v.max(dv).min(1.0 - dv);
+                (u - du / 2.0, u + du / 2.0, v - dv / 2.0, v + dv / 2.0)
+            }
+            None => (u, u + 1e-4, v, v + 1e-4),
+        };
+        let height_at = |u_at: f64, v_at: f64| {
+            let p_at = p + (u_at - u) * hit_record.dpdu + (v_at - v) * hit_record.dpdv;
+            self.height.value(u_at, v_at, p_at)[0]
+        };
+        let dhdu = self.scale * (height_at(u1, v) - height_at(u0, v)) / (u1 - u0);
+        let dhdv = self.scale * (height_at(u, v1) - height_at(u, v0)) / (v1 - v0);
+
+        let normal = hit_record.outward_normal();
+        let dpdu = hit_record.dpdu + dhdu * normal;
+        let dpdv = hit_record.dpdv + dhdv * normal;
+        let mut bumped = dpdu.cross(dpdv).unit_vector();
+        if bumped.dot(normal) < 0.0 {
+            bumped = -bumped;
+        }
+
+        let mut shading = hit_record.clone();
+        shading.normal = if hit_record.front_face {
+            bumped
+        } else {
+            -bumped
+        };
+        shading.dpdu = dpdu;
+        shading.dpdv = dpdv;
+
+        self.base.scatter(r_in, &shading, sampler)
+    }
+
+    fn interface_ior(&self, lambda: f64) -> Option<Interface> {
+        self.base.interface_ior(lambda)
+    }
+}
+
+// Translucent material such as skin, wax or marble, rendered with a volumetric random walk inside
+// a closed object. The boundary behaves like smooth `Dielectric` glass. Inside, every step samples
+// a free-flight distance: if it falls short of the next boundary hit, the path scatters at that
+// point in the medium, otherwise it reaches the boundary and refracts out or reflects back in.
+pub struct Subsurface {
+    interface: Dielectric,
+    albedo: Vector3,         // Single-scattering albedo, sigma_s / sigma_t
+    mean_free_path: Vector3, // Average distance between scattering events, 1 / sigma_t
+    anisotropy: f64,         // Henyey-Greenstein asymmetry, from -1 (back) to 1 (forward)
+}
+
+impl Subsurface {
+    pub fn new(
+        albedo: Vector3,
+        mean_free_path: Vector3,
+        refraction_index: f64,
+        anisotropy: f64,
+    ) -> Subsurface {
+        Subsurface {
+            interface: Dielectric::new(refraction_index),
+            albedo,
+            mean_free_path,
+            anisotropy: anisotropy.clamp(-0.99, 0.99),
+        }
+    }
+
+    // Builds the material from scattering and absorption coefficients per unit of distance.
+    pub fn with_coefficients(
+        sigma_s: Vector3,
+        sigma_a: Vector3,
+        refraction_index: f64,
+        anisotropy: f64,
+    ) -> Subsurface {
+        let sigma_t = sigma_s + sigma_a;
+        Subsurface::new(
+            sigma_s / sigma_t,
+            Vector3::new(1.0, 1.0, 1.0) / sigma_t,
+            refraction_index,
+            anisotropy,
+        )
+    }
+
+    // Samples a direction from the Henyey-Greenstein phase function around `direction`.
+    fn sample_phase(&self, direction: Vector3, u: (f64, f64)) -> Vector3 {
+        let g = self.anisotropy;
+        let xi = u.0;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * u.1;
+
+        Onb::new(direction).to_world(Vector3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
+
+impl Scatterable for Subsurface {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vector3, Ray)> {
+        if hit_record.front_face {
+            return self.interface.scatter(r_in, hit_record, sampler);
+        }
+
+        // Coefficients in the same terms as the radiance carried by the path.
+        let albedo = r_in.attenuation(self.albedo);
+        let mean_free_path = r_in.attenuation(self.mean_free_path);
+        let sigma_t = Vector3::new(1.0, 1.0, 1.0) / mean_free_path;
+
+        // Sample the free-flight distance from one channel picked at random, and weight by the
+        // average pdf over all channels (one-sample MIS) to keep colored media unbiased.
+        let channel = ((sampler.get_1d() * 3.0) as usize).min(2);
+        let distance = -(1.0 - sampler.get_1d()).ln() / sigma_t[channel];
+        let boundary = hit_record.t * r_in.direction.length();
+
+        let transmittance = |d: f64| {
+            Vector3::new(
+                (-sigma_t[0] * d).exp(),
+                (-sigma_t[1] * d).exp(),
+                (-sigma_t[2] * d).exp(),
+            )
+        };
+
+        if distance < boundary {
+            let t = transmittance(distance);
+            let pdf = (sigma_t[0] * t[0] + sigma_t[1] * t[1] + sigma_t[2] * t[2]) / 3.0;
+            let weight = (albedo * sigma_t * t) / pdf;
+
+            let unit_direction = r_in.direction.unit_vector();
+            let origin = r_in.origin + distance * unit_direction;
+            let direction = self.sample_phase(unit_direction, sampler.get_2d());
+            return Some((weight, r_in.spawn(origin, direction)));
+        }
+
+        let t = transmittance(boundary);
+        let probability = (t[0] + t[1] + t[2]) / 3.0;
+        let (attenuation, scattered) = self.interface.scatter(r_in, hit_record, sampler)?;
+
+        Some(((t / probability) * attenuation, scattered))
+    }
+
+    fn interface_ior(&self, lambda: f64) -> Option<Interface> {
+        self.interface.interface_ior(lambda)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        animation::{Interpolation, Keyframes},
+        image::Image,
+        sampler::{IndependentSampler, Sampler},
+        texture::ImageTexture,
+    };
+
+    #[test]
+    fn animated_interpolates_parameters() {
+        let albedo = Keyframes::new(
+            vec![
+                (0.0, Vector3::new(0.0, 0.0, 0.0)),
+                (2.0, Vector3::new(1.0, 0.5, 0.0)),
+            ],
+            Interpolation::Linear,
+        );
+        let material = Animated::new(move |time| Lambertian::new(albedo.at(time)));
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let hit_record = HitRecord::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            normal,
+            1.0,
+            Arc::new(Lambertian::new(normal)),
+        );
+
+        let mut sampler = IndependentSampler::new(0);
+        let mut r_in = Ray::new(normal, -normal);
+        r_in.time = 1.0;
+        let (attenuation, _) = material.scatter(&r_in, &hit_record, &mut sampler).unwrap();
+        assert_eq!(
+            (attenuation[0], attenuation[1], attenuation[2]),
+            (0.5, 0.25, 0.0)
+        );
+    }
+
+    #[test]
+    fn thin_film_over_metal_only_reflects() {
+        let metal: Arc<dyn Scatterable> = Arc::new(Metal::new(Vector3::new(0.9, 0.6, 0.3), 0.0));
+        let film = ThinFilm::new(metal.clone(), 300.0, 1.33);
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let mut hit_record = HitRecord::new(Vector3::new(0.0, 0.0, 0.0), normal, 1.0, metal);
+        hit_record.front_face = true;
+
+        let mut sampler = IndependentSampler::new(7);
+        for i in 0..1000 {
+            sampler.start_pixel_sample((0, 0), i);
+            let (x, y) = sampler.get_2d();
+            let direction = Vector3::new(x - 0.5, y - 0.5, -0.05 - sampler.get_1d());
+            let r_in = Ray::new(-direction, direction);
+
+            if let Some((_, scattered)) = film.scatter(&r_in, &hit_record, &mut sampler) {
+                assert!(scattered.direction.dot(normal) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "positive reference distance")]
+    fn tinted_glass_rejects_zero_distance() {
+        Dielectric::tinted(1.5, Vector3::new(0.8, 0.9, 1.0), 0.0);
+    }
+
+    #[test]
+    fn image_height_ramp_tilts_shading_normal() {
+        // Heights rise linearly along u by 100/99 per unit, one step per texel.
+        let width = 100;
+        let ramp = (0..width).map(|i| i as f64 / (width - 1) as f64).collect();
+        let height = Arc::new(ImageTexture::linear(Image::new(width, 1, 1, ramp)));
+        let mirror: Arc<dyn Scatterable> = Arc::new(Metal::new(Vector3::new(1.0, 1.0, 1.0), 0.0));
+        let bumped = BumpMapped::new(mirror.clone(), height, 0.1);
+
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let slope = 0.1 * width as f64 / (width - 1) as f64;
+        let expected_normal = Vector3::new(-slope, 0.0, 1.0).unit_vector();
+        let direction = Vector3::new(0.0, 0.0, -1.0);
+        let expected = direction - 2.0 * direction.dot(expected_normal) * expected_normal;
+
+        let mut sampler = IndependentSampler::new(3);
+        for u in [0.001, 0.2, 0.4049, 0.5, 0.77, 0.999] {
+            let mut hit_record =
+                HitRecord::new(Vector3::new(0.0, 0.0, 0.0), normal, 1.0, mirror.clone());
+            hit_record.front_face = true;
+            hit_record.u = u;
+            hit_record.v = 0.5;
+            hit_record.dpdu = Vector3::new(1.0, 0.0, 0.0);
+            hit_record.dpdv = Vector3::new(0.0, 1.0, 0.0);
+
+            let r_in = Ray::new(-direction, direction);
+            let (_, scattered) = bumped.scatter(&r_in, &hit_record, &mut sampler).unwrap();
+            let scattered = scattered.direction.unit_vector();
+            assert!(
+                (scattered - expected).length() < 1e-6,
+                "reflected along x = {} instead of {} at u = {}",
+                scattered[0],
+                expected[0],
+                u
+            );
+        }
+    }
 }
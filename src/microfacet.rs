@@ -0,0 +1,285 @@
+use std::f64::consts::PI;
+
+use crate::vec3::Vector3;
+
+// Trowbridge-Reitz (GGX) microfacet distribution. Directions are expressed in the local shading
+// frame, where +Z is the macro-surface normal and X/Y are the tangent directions along which the
+// two roughness values apply.
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> TrowbridgeReitz {
+        TrowbridgeReitz { alpha_x, alpha_y }
+    }
+
+    // Maps a perceptually linear roughness in [0, 1] to the distribution's alpha parameter.
+    pub fn roughness_to_alpha(roughness: f64) -> f64 {
+        roughness.clamp(0.0, 1.0).powi(2)
+    }
+
+    // Below this roughness the distribution is handled as a perfect mirror, since sampling it
+    // would be numerically unstable.
+    pub fn effectively_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    pub fn d(&self, wm: Vector3) -> f64 {
+        let cos2_theta = wm[2] * wm[2];
+        if cos2_theta == 0.0 {
+            return 0.0;
+        }
+
+        let e = (wm[0] * wm[0] / (self.alpha_x * self.alpha_x)
+            + wm[1] * wm[1] / (self.alpha_y * self.alpha_y))
+            / cos2_theta;
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos2_theta * cos2_theta * (1.0 + e).powi(2))
+    }
+
+    pub fn lambda(&self, w: Vector3) -> f64 {
+        let cos2_theta = w[2] * w[2];
+        if cos2_theta == 0.0 {
+            return f64::INFINITY;
+        }
+
+        let alpha2_tan2_theta = (self.alpha_x * self.alpha_x * w[0] * w[0]
+            + self.alpha_y * self.alpha_y * w[1] * w[1])
+            / cos2_theta;
+        ((1.0 + alpha2_tan2_theta).sqrt() - 1.0) / 2.0
+    }
+
+    // Smith masking function for a single direction.
+    pub fn g1(&self, w: Vector3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height-correlated Smith masking-shadowing function.
+    pub fn g(&self, wo: Vector3, wi: Vector3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Samples a microfacet normal from the distribution of normals visible from `w`, following
+    // Heitz 2018, "Sampling the GGX Distribution of Visible Normals".
+    pub fn sample_wm(&self, w: Vector3, u: (f64, f64)) -> Vector3 {
+        // Transform w to the hemispherical configuration.
+        let mut wh = Vector3::new(self.alpha_x * w[0], self.alpha_y * w[1], w[2]).unit_vector();
+        if wh[2] < 0.0 {
+            wh = -wh;
+        }
+
+        // Find an orthonormal basis for the visible normal sampling.
+        let t1 = if wh[2] < 0.99999 {
+            Vector3::new(0.0, 0.0, 1.0).cross(wh).unit_vector()
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = wh.cross(t1);
+
+        // Generate a uniformly distributed point on the unit disk and warp it onto the projection
+        // of the visible hemisphere.
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let px = r * phi.cos();
+        let mut py = r * phi.sin();
+        let h = (1.0 - px * px).sqrt();
+        let s = (1.0 + wh[2]) / 2.0;
+        py = (1.0 - s) * h + s * py;
+
+        // Reproject onto the hemisphere and transform the normal back to the ellipsoid.
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+        let nh = px * t1 + py * t2 + pz * wh;
+        Vector3::new(self.alpha_x * nh[0], self.alpha_y * nh[1], nh[2].max(1e-6)).unit_vector()
+    }
+}
+
+// Unpolarized Fresnel reflectance at a smooth interface between two dielectrics, where `eta` is
+// the ratio of the index of refraction on the transmitted side over the incident side. A negative
+// cosine means the light arrives from the transmitted side.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let mut cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let mut eta = eta;
+    if cos_theta_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+    }
+
+    let sin2_theta_i = 1.0 - cos_theta_i * cos_theta_i;
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        // Total internal reflection.
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+// Unpolarized Fresnel reflectance of a conductor with complex index of refraction `eta + i k`,
+// relative to the medium the light arrives from.
+pub fn fresnel_conductor(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+    let cos2_theta_i = cos_theta_i * cos_theta_i;
+    let sin2_theta_i = 1.0 - cos2_theta_i;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2_theta_i;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2_theta_i;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2_theta_i * a2_plus_b2 + sin2_theta_i * sin2_theta_i;
+    let t4 = t2 * sin2_theta_i;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+// Per-channel conductor Fresnel reflectance for RGB indices of refraction.
+pub fn fresnel_conductor_rgb(cos_theta_i: f64, eta: Vector3, k: Vector3) -> Vector3 {
+    Vector3::new(
+        fresnel_conductor(cos_theta_i, eta[0], k[0]),
+        fresnel_conductor(cos_theta_i, eta[1], k[1]),
+        fresnel_conductor(cos_theta_i, eta[2], k[2]),
+    )
+}
+
+// Reflectance of a thin film of thickness `thickness` (in nanometers) and index `film_eta`
+// deposited on a substrate with complex index `eta + i k`, with light of wavelength `lambda`
+// arriving from air. Sums the multiple reflections inside the film with the Airy formula, which
+// gives the iridescence of soap bubbles, oil slicks and anti-reflective coatings.
+pub fn thin_film_reflectance(
+    cos_theta_i: f64,
+    lambda: f64,
+    thickness: f64,
+    film_eta: f64,
+    eta: f64,
+    k: f64,
+) -> f64 {
+    let cos0 = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_theta1 = (1.0 - cos0 * cos0) / (film_eta * film_eta);
+    if sin2_theta1 >= 1.0 {
+        return 1.0;
+    }
+    let cos1 = (1.0 - sin2_theta1).sqrt();
+
+    let n0 = Complex::new(1.0, 0.0);
+    let n1 = Complex::new(film_eta, 0.0);
+    let n2 = Complex::new(eta, k);
+    let c0 = Complex::new(cos0, 0.0);
+    let c1 = Complex::new(cos1, 0.0);
+    // Snell's law into the (possibly absorbing) substrate: n2 sin2 = n1 sin1.
+    let sin2 = Complex::new(film_eta * sin2_theta1.sqrt(), 0.0).div(n2);
+    let c2 = Complex::new(1.0, 0.0).sub(sin2.mul(sin2)).sqrt();
+
+    let fresnel = |a: Complex, ca: Complex, b: Complex, cb: Complex| {
+        let s = a.mul(ca).sub(b.mul(cb)).div(a.mul(ca).add(b.mul(cb)));
+        let p = b.mul(ca).sub(a.mul(cb)).div(b.mul(ca).add(a.mul(cb)));
+        (s, p)
+    };
+    let (r01_s, r01_p) = fresnel(n0, c0, n1, c1);
+    let (r12_s, r12_p) = fresnel(n1, c1, n2, c2);
+
+    let phase = 4.0 * PI * film_eta * thickness * cos1 / lambda;
+    let shift = Complex::new(phase.cos(), phase.sin());
+    let airy = |r01: Complex, r12: Complex| {
+        let r12_shifted = r12.mul(shift);
+        r01.add(r12_shifted)
+            .div(Complex::new(1.0, 0.0).add(r01.mul(r12_shifted)))
+            .norm_sqr()
+    };
+
+    ((airy(r01_s, r12_s) + airy(r01_p, r12_p)) / 2.0).clamp(0.0, 1.0)
+}
+
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+
+    fn div(self, other: Complex) -> Complex {
+        let denominator = other.norm_sqr();
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / denominator,
+            (self.im * other.re - self.re * other.im) / denominator,
+        )
+    }
+
+    fn norm_sqr(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // Principal square root.
+    fn sqrt(self) -> Complex {
+        let norm = self.norm_sqr().sqrt();
+        let re = ((norm + self.re) / 2.0).max(0.0).sqrt();
+        let im = ((norm - self.re) / 2.0).max(0.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresnel_conductor_at_normal_incidence() {
+        // Gold at 650 nm.
+        let (eta, k) = (0.166, 3.15);
+        let expected = ((eta - 1.0) * (eta - 1.0) + k * k) / ((eta + 1.0) * (eta + 1.0) + k * k);
+        assert!((fresnel_conductor(1.0, eta, k) - expected).abs() < 1e-9);
+        // With no absorption it reduces to the dielectric formula.
+        assert!((fresnel_conductor(1.0, 1.5, 0.0) - 0.04).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fresnel_dielectric_at_normal_incidence() {
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-9);
+        // Leaving the glass reflects the same amount.
+        assert!((fresnel_dielectric(-1.0, 1.5) - 0.04).abs() < 1e-9);
+        assert_eq!(fresnel_dielectric(1.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn thin_film_of_zero_thickness_is_bare_substrate() {
+        for cos_theta in [1.0, 0.7, 0.3, 0.05] {
+            for lambda in [450.0, 550.0, 650.0] {
+                // Without a film the reflectance must be the substrate's own Fresnel term, so
+                // the film neither creates nor absorbs energy.
+                let glass = thin_film_reflectance(cos_theta, lambda, 0.0, 1.33, 1.5, 0.0);
+                assert!((glass - fresnel_dielectric(cos_theta, 1.5)).abs() < 1e-9);
+                let gold = thin_film_reflectance(cos_theta, lambda, 0.0, 1.33, 0.166, 3.15);
+                assert!((gold - fresnel_conductor(cos_theta, 0.166, 3.15)).abs() < 1e-9);
+                assert!((0.0..=1.0).contains(&glass) && (0.0..=1.0).contains(&gold));
+            }
+        }
+    }
+}
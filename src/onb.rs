@@ -0,0 +1,59 @@
+use crate::vec3::Vector3;
+
+// Orthonormal basis whose `w` axis is aligned with a given direction, used to move vectors in
+// and out of the local shading frame around a surface normal.
+pub struct Onb {
+    axis: [Vector3; 3],
+}
+
+impl Onb {
+    pub fn new(n: Vector3) -> Onb {
+        let w = n.unit_vector();
+        let a = if w[0].abs() > 0.9 {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).unit_vector();
+        let u = w.cross(v);
+
+        Onb { axis: [u, v, w] }
+    }
+
+    // Basis around `n` whose `u` axis follows `tangent` projected onto the plane normal to `n`.
+    pub fn from_tangent(n: Vector3, tangent: Vector3) -> Onb {
+        let w = n.unit_vector();
+        let projected = tangent - tangent.dot(w) * w;
+        if projected.near_zero() {
+            return Onb::new(n);
+        }
+        let u = projected.unit_vector();
+        let v = w.cross(u);
+
+        Onb { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> Vector3 {
+        self.axis[0]
+    }
+
+    pub fn v(&self) -> Vector3 {
+        self.axis[1]
+    }
+
+    pub fn w(&self) -> Vector3 {
+        self.axis[2]
+    }
+
+    pub fn to_world(&self, local: Vector3) -> Vector3 {
+        local[0] * self.axis[0] + local[1] * self.axis[1] + local[2] * self.axis[2]
+    }
+
+    pub fn to_local(&self, world: Vector3) -> Vector3 {
+        Vector3::new(
+            world.dot(self.axis[0]),
+            world.dot(self.axis[1]),
+            world.dot(self.axis[2]),
+        )
+    }
+}
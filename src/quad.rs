@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use crate::{
+    hittable::{HitRecord, Hittable},
+    materials::Scatterable,
+    ray::Ray,
+    vec3::Vector3,
+};
+
+// Parallelogram spanned by the edges `u` and `v` from the corner `q`. Texture coordinates run
+// from 0 to 1 along each edge.
+pub struct Quad {
+    q: Vector3,
+    u: Vector3,
+    v: Vector3,
+    w: Vector3,
+    normal: Vector3,
+    d: f64,
+    material: Arc<dyn Scatterable>,
+}
+
+impl Quad {
+    pub fn new(q: Vector3, u: Vector3, v: Vector3, material: Arc<dyn Scatterable>) -> Quad {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+        let d = normal.dot(q);
+        let w = n / n.dot(n);
+
+        Quad {
+            q,
+            u,
+            v,
+            w,
+            normal,
+            d,
+            material,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let denom = self.normal.dot(r.direction);
+
+        // No hit if the ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        // No hit if the hit point parameter t is outside the ray interval.
+        let t = (self.d - self.normal.dot(r.origin)) / denom;
+        if t <= ray_tmin || ray_tmax <= t {
+            return None;
+        }
+
+        // Determine if the hit point lies within the planar shape using its plane coordinates.
+        let intersection = r.at(t);
+        let planar_hitpt = intersection - self.q;
+        let alpha = self.w.dot(planar_hitpt.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar_hitpt));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(
+            intersection,
+            Vector3::new(0.0, 0.0, 0.0),
+            t,
+            self.material.clone(),
+        );
+        rec.set_face_normal(r, self.normal);
+        rec.u = alpha;
+        rec.v = beta;
+        rec.dpdu = self.u;
+        rec.dpdv = self.v;
+
+        Some(rec)
+    }
+}
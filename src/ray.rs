@@ -1,13 +1,39 @@
-use crate::vec3::Vector3;
+use crate::{spectrum::Wavelengths, vec3::Vector3};
 
 pub struct Ray {
     pub origin: Vector3,
     pub direction: Vector3,
+    pub wavelengths: Option<Wavelengths>, // Set when rendering in spectral mode
+    pub time: f64,                        // Time at which the ray sees the scene, in frames
 }
 
 impl Ray {
     pub fn new(origin: Vector3, direction: Vector3) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            wavelengths: None,
+            time: 0.0,
+        }
+    }
+
+    // Continues the path of this ray from a new origin and direction, e.g. after scattering.
+    pub fn spawn(&self, origin: Vector3, direction: Vector3) -> Ray {
+        Ray {
+            origin,
+            direction,
+            wavelengths: self.wavelengths,
+            time: self.time,
+        }
+    }
+
+    // Expresses an RGB attenuation in the same terms as the radiance carried by this ray, which
+    // are values at its wavelengths in spectral mode.
+    pub fn attenuation(&self, rgb: Vector3) -> Vector3 {
+        match self.wavelengths {
+            Some(wavelengths) => wavelengths.upsample(rgb),
+            None => rgb,
+        }
     }
 
     pub fn at(&self, t: f64) -> Vector3 {
@@ -0,0 +1,598 @@
+use std::{f64::consts::PI, sync::OnceLock};
+
+use rand::Rng;
+
+use crate::{pcg::Pcg32, vec3::Vector3};
+
+// Largest f64 below one, so that samples stay in [0, 1).
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// Source of the sample values used while rendering a pixel. Each call for a value moves on to the
+// next dimension of the sample, so samples with the same index in the same pixel always read the
+// same sequence of dimensions. Well-distributed samplers spread each dimension evenly over the
+// samples of a pixel, which lowers noise at the same sample count.
+pub trait Sampler {
+    // Starts the sample with the given index for a pixel, rewinding to the first dimension.
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), sample_index: u32);
+
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+// Sampling strategy, used by the camera to create a sampler for each render. Every sampler gives
+// the same values for the same seed, pixel and sample index, whatever order pixels are rendered
+// in, so renders are reproducible.
+#[derive(Clone, Copy)]
+pub enum SamplerKind {
+    // Uniform random values with no relation between samples.
+    Independent,
+    // A grid of strata per dimension, with one sample in each. Jittering places samples randomly
+    // within their stratum instead of at its center.
+    Stratified { jitter: bool },
+    // Halton sequence, with each pixel's copy scrambled independently.
+    Halton,
+    // Sobol (0, 2)-sequence for each pair of dimensions, padded with Owen-scrambled copies.
+    Sobol,
+    // Low-discrepancy samples shifted per pixel by a blue-noise mask, so that the error left at
+    // low sample counts looks like fine blue noise instead of clumps.
+    BlueNoise,
+}
+
+impl SamplerKind {
+    pub fn create(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified { jitter } => {
+                Box::new(StratifiedSampler::new(samples_per_pixel, *jitter, seed))
+            }
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+pub struct IndependentSampler {
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> IndependentSampler {
+        IndependentSampler {
+            seed,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), sample_index: u32) {
+        self.rng = pixel_sample_rng(self.seed, pixel, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
+
+pub struct StratifiedSampler {
+    x_samples: u32,
+    y_samples: u32,
+    jitter: bool,
+    seed: u64,
+    rng: Pcg32,
+    pixel: (i32, i32),
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    // Splits the samples of a pixel into a grid as close to square as their count allows.
+    pub fn new(samples_per_pixel: u32, jitter: bool, seed: u64) -> StratifiedSampler {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let mut x_samples = (samples_per_pixel as f64).sqrt() as u32;
+        while !samples_per_pixel.is_multiple_of(x_samples) {
+            x_samples -= 1;
+        }
+
+        StratifiedSampler {
+            x_samples,
+            y_samples: samples_per_pixel / x_samples,
+            jitter,
+            seed,
+            rng: Pcg32::new(seed, 0),
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn offset(&mut self) -> f64 {
+        if self.jitter {
+            self.rng.gen()
+        } else {
+            0.5
+        }
+    }
+
+    // Stratum of the current sample in this dimension. Strata are shuffled differently for every
+    // pixel and dimension so that dimensions aren't correlated with each other.
+    fn stratum(&mut self, count: u32) -> u32 {
+        let seed = hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+        ]);
+        self.dimension += 1;
+        permutation_element(self.sample_index % count, count, seed as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), sample_index: u32) {
+        self.rng = pixel_sample_rng(self.seed, pixel, sample_index);
+        self.pixel = pixel;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let count = self.x_samples * self.y_samples;
+        let stratum = self.stratum(count);
+        ((stratum as f64 + self.offset()) / count as f64).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let stratum = self.stratum(self.x_samples * self.y_samples);
+        let x = stratum % self.x_samples;
+        let y = stratum / self.x_samples;
+        (
+            ((x as f64 + self.offset()) / self.x_samples as f64).min(ONE_MINUS_EPSILON),
+            ((y as f64 + self.offset()) / self.y_samples as f64).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+// Number of dimensions covered by the Halton sequence, one prime base each. Further dimensions
+// fall back to hashed random values.
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: (i32, i32),
+    sample_index: u32,
+    dimension: usize,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> HaltonSampler {
+        HaltonSampler {
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next(&mut self) -> f64 {
+        let seed = hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+        ]);
+        let value = match PRIMES.get(self.dimension) {
+            Some(&base) => owen_scrambled_radical_inverse(base, self.sample_index as u64, seed),
+            None => hashed_uniform(&[seed, self.sample_index as u64]),
+        };
+        self.dimension += 1;
+        value
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), sample_index: u32) {
+        self.pixel = pixel;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.next()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.next(), self.next())
+    }
+}
+
+pub struct SobolSampler {
+    seed: u64,
+    pixel: (i32, i32),
+    sample_index: u32,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> SobolSampler {
+        SobolSampler {
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    // Seed of the current dimension in this pixel, moving on to the next dimension.
+    fn seed(&mut self) -> u64 {
+        let seed = hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension,
+        ]);
+        self.dimension += 1;
+        seed
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), sample_index: u32) {
+        self.pixel = pixel;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let seed = self.seed();
+        // Shuffle the sample order so that each dimension pairs up differently with the others.
+        let index = nested_uniform_scramble(self.sample_index, seed as u32);
+        sobol_owen(index, 0, (seed >> 32) as u32)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let seed = self.seed();
+        let index = nested_uniform_scramble(self.sample_index, seed as u32);
+        let seed_y = mix_bits(seed);
+        (
+            sobol_owen(index, 0, (seed >> 32) as u32),
+            sobol_owen(index, 1, (seed_y >> 32) as u32),
+        )
+    }
+}
+
+// Side of the tiled blue-noise mask, in pixels.
+const BLUE_NOISE_SIZE: usize = 64;
+
+pub struct BlueNoiseSampler {
+    seed: u64,
+    pixel: (i32, i32),
+    sample_index: u32,
+    dimension: u64,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> BlueNoiseSampler {
+        BlueNoiseSampler {
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    // Mask value for this pixel, with the mask shifted by a different amount for every dimension
+    // so that dimensions don't share the same pattern.
+    fn mask(&self, salt: u64) -> f64 {
+        let offset = hash(&[self.seed, self.dimension, salt]);
+        let size = BLUE_NOISE_SIZE as u64;
+        let x = (self.pixel.0 as u64).wrapping_add(offset) % size;
+        let y = (self.pixel.1 as u64).wrapping_add(offset >> 32) % size;
+        blue_noise_mask()[y as usize * BLUE_NOISE_SIZE + x as usize]
+    }
+
+    // Shifts a sample by the mask value, wrapping around.
+    fn rotate(value: f64, shift: f64) -> f64 {
+        let rotated = value + shift;
+        (if rotated >= 1.0 {
+            rotated - 1.0
+        } else {
+            rotated
+        })
+        .min(ONE_MINUS_EPSILON)
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), sample_index: u32) {
+        self.pixel = pixel;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        // The sequence itself is shared by all pixels; only the shift varies between them.
+        let seed = hash(&[self.seed, self.dimension]);
+        let index = nested_uniform_scramble(self.sample_index, seed as u32);
+        let value = sobol_owen(index, 0, (seed >> 32) as u32);
+        let shifted = BlueNoiseSampler::rotate(value, self.mask(0));
+        self.dimension += 1;
+        shifted
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let seed = hash(&[self.seed, self.dimension]);
+        let index = nested_uniform_scramble(self.sample_index, seed as u32);
+        let seed_y = mix_bits(seed);
+        let x = sobol_owen(index, 0, (seed >> 32) as u32);
+        let y = sobol_owen(index, 1, (seed_y >> 32) as u32);
+        let shifted = (
+            BlueNoiseSampler::rotate(x, self.mask(0)),
+            BlueNoiseSampler::rotate(y, self.mask(1)),
+        );
+        self.dimension += 1;
+        shifted
+    }
+}
+
+// Tileable blue-noise threshold mask with values evenly spread over [0, 1), generated once with
+// Ulichney's void-and-cluster method.
+fn blue_noise_mask() -> &'static [f64] {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    MASK.get_or_init(|| {
+        let size = BLUE_NOISE_SIZE;
+        let count = size * size;
+
+        // Gaussian energy of every pixel from a single point, on a torus so the mask tiles.
+        let sigma = 1.9;
+        let kernel: Vec<f64> = (0..count)
+            .map(|i| {
+                let wrap = |d: usize| d.min(size - d) as f64;
+                let (dx, dy) = (wrap(i % size), wrap(i / size));
+                (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+            })
+            .collect();
+        let splat = |energy: &mut [f64], at: usize, sign: f64| {
+            let (ax, ay) = (at % size, at / size);
+            for (i, e) in energy.iter_mut().enumerate() {
+                let dx = (i % size + size - ax) % size;
+                let dy = (i / size + size - ay) % size;
+                *e += sign * kernel[dy * size + dx];
+            }
+        };
+        // Tightest cluster among set pixels, or largest void among empty ones.
+        let tightest = |energy: &[f64], pattern: &[bool]| {
+            (0..count)
+                .filter(|&i| pattern[i])
+                .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap()
+        };
+        let largest_void = |energy: &[f64], pattern: &[bool]| {
+            (0..count)
+                .filter(|&i| !pattern[i])
+                .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap()
+        };
+
+        // Start from a sparse random pattern and relax it by moving points from the tightest
+        // cluster to the largest void until that no longer changes anything.
+        // A fixed seed keeps the mask the same on every run.
+        let mut rng = Pcg32::new(0, 0);
+        let initial_count = count / 10;
+        let mut pattern = vec![false; count];
+        let mut energy = vec![0.0; count];
+        let mut placed = 0;
+        while placed < initial_count {
+            let i = rng.gen_range(0..count);
+            if !pattern[i] {
+                pattern[i] = true;
+                splat(&mut energy, i, 1.0);
+                placed += 1;
+            }
+        }
+        loop {
+            let cluster = tightest(&energy, &pattern);
+            pattern[cluster] = false;
+            splat(&mut energy, cluster, -1.0);
+            let void = largest_void(&energy, &pattern);
+            pattern[void] = true;
+            splat(&mut energy, void, 1.0);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut rank = vec![0usize; count];
+
+        // Rank the initial points by removing them from the tightest cluster down.
+        let mut removing = pattern.clone();
+        let mut removing_energy = energy.clone();
+        for r in (0..initial_count).rev() {
+            let cluster = tightest(&removing_energy, &removing);
+            removing[cluster] = false;
+            splat(&mut removing_energy, cluster, -1.0);
+            rank[cluster] = r;
+        }
+
+        // Rank the remaining pixels by filling the largest void each time.
+        for r in initial_count..count {
+            let void = largest_void(&energy, &pattern);
+            pattern[void] = true;
+            splat(&mut energy, void, 1.0);
+            rank[void] = r;
+        }
+
+        rank.iter()
+            .map(|&r| (r as f64 + 0.5) / count as f64)
+            .collect()
+    })
+}
+
+// Uniform point in the unit disk, using Shirley's concentric mapping so that stratified samples
+// stay stratified.
+pub fn sample_unit_disk(u: (f64, f64)) -> Vector3 {
+    let (x, y) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return Vector3::new(0.0, 0.0, 0.0);
+    }
+
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, PI / 4.0 * (y / x))
+    } else {
+        (y, PI / 2.0 - PI / 4.0 * (x / y))
+    };
+    Vector3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+// Uniform point on the unit sphere.
+pub fn sample_unit_sphere(u: (f64, f64)) -> Vector3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// Generator for the random values of one sample of a pixel, independent of every other sample.
+fn pixel_sample_rng(seed: u64, pixel: (i32, i32), sample_index: u32) -> Pcg32 {
+    Pcg32::new(
+        hash(&[seed, pixel.0 as u64, pixel.1 as u64]),
+        sample_index as u64,
+    )
+}
+
+// Finalizer from MurmurHash3-style mixing, spreading every input bit over the output.
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5d329728ea185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81dadef4bc2dd44d);
+    v ^= v >> 33;
+    v
+}
+
+pub(crate) fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x9e3779b97f4a7c15, |h, &v| mix_bits(h ^ mix_bits(v)))
+}
+
+// Hash of the values mapped to [0, 1).
+pub(crate) fn hashed_uniform(values: &[u64]) -> f64 {
+    (hash(values) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// Element `i` of a random permutation of 0..l picked by `p`, from Kensler 2013, "Correlated
+// Multi-Jittered Sampling".
+fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
+
+// Radical inverse of `a` in `base` with every digit permuted depending on the digits before it,
+// which is Owen scrambling in an arbitrary base.
+fn owen_scrambled_radical_inverse(base: u64, mut a: u64, seed: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits: u64 = 0;
+    // Keep going past the last non-zero digit, since scrambled zeros aren't zero.
+    while 1.0 - inv_base_m < 1.0 {
+        let next = a / base;
+        let digit = a - next * base;
+        let digit_hash = mix_bits(seed ^ reversed_digits) as u32;
+        let digit = permutation_element(digit as u32, base as u32, digit_hash) as u64;
+        reversed_digits = reversed_digits * base + digit;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    (inv_base_m * reversed_digits as f64).min(ONE_MINUS_EPSILON)
+}
+
+// Hash-based Owen scrambling of the bits of `v`, from Burley 2020, "Practical Hash-based Owen
+// Scrambling".
+fn nested_uniform_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x05526c56);
+    v ^= v.wrapping_mul(0x53a22864);
+    v.reverse_bits()
+}
+
+// Owen-scrambled value of the first (van der Corput) or second dimension of the Sobol sequence.
+fn sobol_owen(index: u32, dimension: usize, seed: u32) -> f64 {
+    let mut v: u32 = 0;
+    let mut column: u32 = 1 << 31;
+    let mut bits = index;
+    while bits != 0 {
+        if bits & 1 != 0 {
+            v ^= column;
+        }
+        bits >>= 1;
+        // The second dimension's generator matrix is Pascal's triangle mod 2.
+        column = if dimension == 0 {
+            column >> 1
+        } else {
+            column ^ (column >> 1)
+        };
+    }
+
+    let v = nested_uniform_scramble(v, seed);
+    (v as f64 / (1u64 << 32) as f64).min(ONE_MINUS_EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sobol_owen_values_lie_in_unit_interval() {
+        let indices = (0..4096).chain([u32::MAX - 1, u32::MAX]);
+        for index in indices {
+            for dimension in 0..2 {
+                for seed in [0, 1, 0xdeadbeef, u32::MAX] {
+                    let value = sobol_owen(index, dimension, seed);
+                    assert!((0.0..1.0).contains(&value), "{} at index {}", value, index);
+                }
+            }
+        }
+    }
+}
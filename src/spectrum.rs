@@ -0,0 +1,261 @@
+use std::sync::OnceLock;
+
+use crate::vec3::Vector3;
+
+// Range of visible wavelengths sampled in spectral mode, in nanometers.
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+// Wavelength at which dispersive materials are evaluated when rendering in RGB (sodium d-line).
+pub const LAMBDA_REFERENCE: f64 = 587.6;
+
+// Dominant wavelengths standing in for the red, green and blue channels when a wavelength
+// dependent effect has to be evaluated in RGB mode.
+pub const RGB_WAVELENGTHS: [f64; 3] = [630.0, 532.0, 465.0];
+
+// Wavelengths carried by a path in spectral mode, one per radiance component. They are picked
+// with hero wavelength sampling: the hero is uniform over the visible range and the others are
+// evenly rotated from it, so a single path covers the whole spectrum.
+#[derive(Clone, Copy)]
+pub struct Wavelengths {
+    lambda: [f64; 3],
+    secondary_terminated: bool,
+}
+
+impl Wavelengths {
+    pub fn sample(u: f64) -> Wavelengths {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + u * range;
+        let mut lambda = [hero; 3];
+        for (i, l) in lambda.iter_mut().enumerate().skip(1) {
+            *l = hero + i as f64 * range / 3.0;
+            if *l > LAMBDA_MAX {
+                *l -= range;
+            }
+        }
+
+        Wavelengths {
+            lambda,
+            secondary_terminated: false,
+        }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub fn lambdas(&self) -> [f64; 3] {
+        self.lambda
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.secondary_terminated
+    }
+
+    // Keeps only the hero wavelength, for events such as dispersive refraction where each
+    // wavelength would continue in a different direction.
+    pub fn terminate_secondary(&self) -> Wavelengths {
+        Wavelengths {
+            lambda: self.lambda,
+            secondary_terminated: true,
+        }
+    }
+
+    // Spectral values of an RGB color at each of the wavelengths.
+    pub fn upsample(&self, rgb: Vector3) -> Vector3 {
+        Vector3::new(
+            rgb_to_spectrum(rgb, self.lambda[0]),
+            rgb_to_spectrum(rgb, self.lambda[1]),
+            rgb_to_spectrum(rgb, self.lambda[2]),
+        )
+    }
+
+    // Weight that moves the estimate of the secondary wavelengths onto the hero once they are
+    // terminated, since the hero then has to account for the whole spectrum on its own.
+    pub fn termination_weight(&self) -> Vector3 {
+        if self.secondary_terminated {
+            Vector3::new(1.0, 1.0, 1.0)
+        } else {
+            Vector3::new(3.0, 0.0, 0.0)
+        }
+    }
+
+    // Converts radiance at these wavelengths to linear sRGB through CIE XYZ.
+    pub fn to_rgb(&self, radiance: Vector3) -> Vector3 {
+        let pdf = 1.0 / (LAMBDA_MAX - LAMBDA_MIN);
+        let mut xyz = Vector3::new(0.0, 0.0, 0.0);
+        for (i, lambda) in self.lambda.iter().enumerate() {
+            xyz += (radiance[i] / (3.0 * pdf)) * cie_xyz(*lambda);
+        }
+
+        xyz_to_rgb(xyz) / white_balance()
+    }
+}
+
+// Index of refraction that may vary with wavelength, giving rise to dispersion.
+#[derive(Clone, Copy)]
+pub enum RefractiveIndex {
+    Constant(f64),
+    // n = a + b / λ², with λ in micrometers.
+    Cauchy { a: f64, b: f64 },
+    // n² = 1 + Σ b_i λ² / (λ² - c_i), with λ in micrometers and c_i in square micrometers.
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl RefractiveIndex {
+    pub fn bk7() -> RefractiveIndex {
+        RefractiveIndex::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    pub fn fused_silica() -> RefractiveIndex {
+        RefractiveIndex::Sellmeier {
+            b: [0.6961663, 0.4079426, 0.8974794],
+            c: [0.00467914826, 0.0135120631, 97.9340025],
+        }
+    }
+
+    pub fn diamond() -> RefractiveIndex {
+        RefractiveIndex::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030625, 0.011236, 0.0],
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, RefractiveIndex::Constant(_))
+    }
+
+    pub fn at(&self, lambda: f64) -> f64 {
+        let micrometers = lambda / 1000.0;
+        let l2 = micrometers * micrometers;
+        match self {
+            RefractiveIndex::Constant(n) => *n,
+            RefractiveIndex::Cauchy { a, b } => a + b / l2,
+            RefractiveIndex::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
+// Basis spectra from Smits 1999, "An RGB-to-Spectrum Conversion for Reflectances", sampled in ten
+// bins evenly spread over [380, 720] nm.
+const SMITS_WHITE: [f64; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f64; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f64; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+fn smits_basis(basis: &[f64; 10], lambda: f64) -> f64 {
+    // Interpolate linearly between bin centers, holding the end values outside the table.
+    let bin_width = (720.0 - 380.0) / 10.0;
+    let x = ((lambda - 380.0) / bin_width - 0.5).clamp(0.0, 9.0);
+    let i = (x as usize).min(8);
+    let t = x - i as f64;
+    (1.0 - t) * basis[i] + t * basis[i + 1]
+}
+
+// Interpolates per-channel RGB data, such as measured indices of refraction, at `lambda` using
+// the dominant wavelength of each channel.
+pub fn rgb_at(values: Vector3, lambda: f64) -> f64 {
+    let [red, green, blue] = RGB_WAVELENGTHS;
+    if lambda <= blue {
+        values[2]
+    } else if lambda <= green {
+        let t = (lambda - blue) / (green - blue);
+        (1.0 - t) * values[2] + t * values[1]
+    } else if lambda <= red {
+        let t = (lambda - green) / (red - green);
+        (1.0 - t) * values[1] + t * values[0]
+    } else {
+        values[0]
+    }
+}
+
+// Smooth reflectance spectrum matching an RGB color, evaluated at `lambda`.
+pub fn rgb_to_spectrum(rgb: Vector3, lambda: f64) -> f64 {
+    let (r, g, b) = (rgb[0], rgb[1], rgb[2]);
+    let basis = |spectrum: &[f64; 10]| smits_basis(spectrum, lambda);
+
+    if r <= g && r <= b {
+        let base = r * basis(&SMITS_WHITE);
+        if g <= b {
+            base + (g - r) * basis(&SMITS_CYAN) + (b - g) * basis(&SMITS_BLUE)
+        } else {
+            base + (b - r) * basis(&SMITS_CYAN) + (g - b) * basis(&SMITS_GREEN)
+        }
+    } else if g <= r && g <= b {
+        let base = g * basis(&SMITS_WHITE);
+        if r <= b {
+            base + (r - g) * basis(&SMITS_MAGENTA) + (b - r) * basis(&SMITS_BLUE)
+        } else {
+            base + (b - g) * basis(&SMITS_MAGENTA) + (r - b) * basis(&SMITS_RED)
+        }
+    } else {
+        let base = b * basis(&SMITS_WHITE);
+        if r <= g {
+            base + (r - b) * basis(&SMITS_YELLOW) + (g - r) * basis(&SMITS_GREEN)
+        } else {
+            base + (g - b) * basis(&SMITS_YELLOW) + (r - g) * basis(&SMITS_RED)
+        }
+    }
+}
+
+// CIE 1931 color matching functions, using the multi-lobe Gaussian fit from Wyman, Sloan and
+// Shirley 2013, "Simple Analytic Approximations to the CIE XYZ Color Matching Functions".
+pub fn cie_xyz(lambda: f64) -> Vector3 {
+    let g = |mu: f64, sigma_low: f64, sigma_high: f64| {
+        let sigma = if lambda < mu { sigma_low } else { sigma_high };
+        let t = (lambda - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+
+    Vector3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+pub fn xyz_to_rgb(xyz: Vector3) -> Vector3 {
+    Vector3::new(
+        3.2406 * xyz[0] - 1.5372 * xyz[1] - 0.4986 * xyz[2],
+        -0.9689 * xyz[0] + 1.8758 * xyz[1] + 0.0415 * xyz[2],
+        0.0557 * xyz[0] - 0.2040 * xyz[1] + 1.0570 * xyz[2],
+    )
+}
+
+// RGB of the upsampled white spectrum, used to scale results so that white maps back to white.
+fn white_balance() -> Vector3 {
+    static WHITE: OnceLock<Vector3> = OnceLock::new();
+    *WHITE.get_or_init(|| {
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        let mut xyz = Vector3::new(0.0, 0.0, 0.0);
+        let mut lambda = LAMBDA_MIN;
+        while lambda <= LAMBDA_MAX {
+            xyz += rgb_to_spectrum(white, lambda) * cie_xyz(lambda);
+            lambda += 1.0;
+        }
+        xyz_to_rgb(xyz)
+    })
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{f64::consts::PI, sync::Arc};
 
 use crate::{
     hittable::{HitRecord, Hittable},
@@ -21,6 +21,31 @@ impl Sphere {
             material,
         }
     }
+
+    fn get_sphere_uv(p: Vector3) -> (f64, f64) {
+        // p: a given point on the sphere of radius one, centered at the origin.
+        // u: returned value [0,1] of angle around the Y axis from X=-1.
+        // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+        let theta = (-p[1]).acos();
+        let phi = (-p[2]).atan2(p[0]) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn get_sphere_tangents(p: Vector3, radius: f64) -> (Vector3, Vector3) {
+        // Partial derivatives of the surface position with respect to the u and v returned by
+        // get_sphere_uv, for a point p on the unit sphere.
+        let sin_theta = (1.0 - p[1] * p[1]).max(1e-16).sqrt();
+        let dpdu = (2.0 * PI * radius) * Vector3::new(p[2], 0.0, -p[0]);
+        let dpdv = (PI * radius)
+            * Vector3::new(
+                -p[0] * p[1] / sin_theta,
+                sin_theta,
+                -p[1] * p[2] / sin_theta,
+            );
+
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
@@ -53,6 +78,8 @@ impl Hittable for Sphere {
         );
         let outward_normal = (rec.p - self.center) / self.radius;
         rec.set_face_normal(r, outward_normal);
+        (rec.u, rec.v) = Sphere::get_sphere_uv(outward_normal);
+        (rec.dpdu, rec.dpdv) = Sphere::get_sphere_tangents(outward_normal, self.radius);
 
         Some(rec)
     }
@@ -0,0 +1,79 @@
+use crate::{image::Image, vec3::Vector3};
+
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: Vector3) -> Vector3;
+
+    // Size of one texel in texture coordinates, for textures that look up a grid of samples.
+    fn texel_size(&self) -> Option<(f64, f64)> {
+        None
+    }
+}
+
+pub struct SolidColor {
+    albedo: Vector3,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Vector3) -> SolidColor {
+        SolidColor { albedo }
+    }
+
+    // Constant for scalar material parameters, which read the first channel of their texture.
+    pub fn scalar(value: f64) -> SolidColor {
+        SolidColor::new(Vector3::new(value, value, value))
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Vector3) -> Vector3 {
+        self.albedo
+    }
+}
+
+pub struct ImageTexture {
+    image: Image,
+    gamma_encoded: bool,
+}
+
+impl ImageTexture {
+    // Color texture. Samples are decoded with the same gamma 2 curve used when writing images.
+    pub fn new(image: Image) -> ImageTexture {
+        ImageTexture {
+            image,
+            gamma_encoded: true,
+        }
+    }
+
+    // Data texture (roughness, heights, masks, ...) whose samples are used as-is.
+    pub fn linear(image: Image) -> ImageTexture {
+        ImageTexture {
+            image,
+            gamma_encoded: false,
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Vector3) -> Vector3 {
+        // Clamp input texture coordinates to [0,1] x [1,0], flipping V to image coordinates.
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0);
+
+        let i = (u * self.image.width() as f64) as usize;
+        let j = (v * self.image.height() as f64) as usize;
+        let pixel = self.image.pixel(i, j);
+
+        if self.gamma_encoded {
+            pixel * pixel
+        } else {
+            pixel
+        }
+    }
+
+    fn texel_size(&self) -> Option<(f64, f64)> {
+        Some((
+            1.0 / self.image.width() as f64,
+            1.0 / self.image.height() as f64,
+        ))
+    }
+}
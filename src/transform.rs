@@ -0,0 +1,169 @@
+use crate::{animation::Keyframes, utils::degrees_to_radians, vec3::Vector3};
+
+// Unit quaternion representing a rotation.
+#[derive(Clone, Copy)]
+pub struct Quaternion {
+    w: f64,
+    xyz: Vector3,
+}
+
+impl Quaternion {
+    pub fn identity() -> Quaternion {
+        Quaternion {
+            w: 1.0,
+            xyz: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // Rotation by `degrees` counterclockwise about `axis`, looking down the axis.
+    pub fn from_axis_angle(axis: Vector3, degrees: f64) -> Quaternion {
+        let half = degrees_to_radians(degrees) / 2.0;
+        Quaternion {
+            w: half.cos(),
+            xyz: axis.unit_vector() * half.sin(),
+        }
+    }
+
+    fn dot(&self, other: Quaternion) -> f64 {
+        self.w * other.w + self.xyz.dot(other.xyz)
+    }
+
+    fn scaled(&self, s: f64) -> Quaternion {
+        Quaternion {
+            w: self.w * s,
+            xyz: self.xyz * s,
+        }
+    }
+
+    fn plus(&self, other: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w + other.w,
+            xyz: self.xyz + other.xyz,
+        }
+    }
+
+    fn normalized(&self) -> Quaternion {
+        self.scaled(1.0 / self.dot(*self).sqrt())
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion {
+            w: self.w,
+            xyz: -self.xyz,
+        }
+    }
+
+    // Composition applying `other` first, then this rotation.
+    pub fn then(&self, other: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * other.w - self.xyz.dot(other.xyz),
+            xyz: self.w * other.xyz + other.w * self.xyz + self.xyz.cross(other.xyz),
+        }
+    }
+
+    pub fn rotate(&self, v: Vector3) -> Vector3 {
+        let t = 2.0 * self.xyz.cross(v);
+        v + self.w * t + self.xyz.cross(t)
+    }
+
+    // Spherical linear interpolation, turning at constant speed along the shorter arc.
+    pub fn slerp(a: Quaternion, b: Quaternion, t: f64) -> Quaternion {
+        // q and -q are the same rotation; pick the one closer to a.
+        let mut cos_theta = a.dot(b);
+        let b = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            b.scaled(-1.0)
+        } else {
+            b
+        };
+
+        // Nearly parallel rotations interpolate linearly to avoid dividing by sin(θ) ≈ 0.
+        if cos_theta > 0.9995 {
+            return a.scaled(1.0 - t).plus(b.scaled(t)).normalized();
+        }
+
+        let theta = cos_theta.acos();
+        let sin_theta = theta.sin();
+        let wa = ((1.0 - t) * theta).sin() / sin_theta;
+        let wb = (t * theta).sin() / sin_theta;
+        a.scaled(wa).plus(b.scaled(wb))
+    }
+}
+
+// Scale, then rotation, then translation.
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub translation: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::identity(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn point(&self, p: Vector3) -> Vector3 {
+        self.rotation.rotate(self.scale * p) + self.translation
+    }
+
+    pub fn vector(&self, v: Vector3) -> Vector3 {
+        self.rotation.rotate(self.scale * v)
+    }
+
+    // Normals transform with the inverse transpose, which divides by the scale instead. The
+    // result is not normalized.
+    pub fn normal(&self, n: Vector3) -> Vector3 {
+        self.rotation.rotate(n / self.scale)
+    }
+
+    pub fn inverse_point(&self, p: Vector3) -> Vector3 {
+        self.rotation.conjugate().rotate(p - self.translation) / self.scale
+    }
+
+    pub fn inverse_vector(&self, v: Vector3) -> Vector3 {
+        self.rotation.conjugate().rotate(v) / self.scale
+    }
+}
+
+// Transform whose translation, rotation and scale are each keyframed over time.
+pub struct AnimatedTransform {
+    translation: Keyframes<Vector3>,
+    rotation: Keyframes<Quaternion>,
+    scale: Keyframes<Vector3>,
+}
+
+impl AnimatedTransform {
+    pub fn new(
+        translation: Keyframes<Vector3>,
+        rotation: Keyframes<Quaternion>,
+        scale: Keyframes<Vector3>,
+    ) -> AnimatedTransform {
+        AnimatedTransform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    // Transform that stays the same at every frame.
+    pub fn fixed(transform: Transform) -> AnimatedTransform {
+        AnimatedTransform::new(
+            Keyframes::constant(transform.translation),
+            Keyframes::constant(transform.rotation),
+            Keyframes::constant(transform.scale),
+        )
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        Transform {
+            translation: self.translation.at(time),
+            rotation: self.rotation.slerp_at(time),
+            scale: self.scale.at(time),
+        }
+    }
+}
@@ -12,8 +12,7 @@ impl Vector3 {
         Vector3 { e: [e0, e1, e2] }
     }
 
-    pub fn random(min: f64, max: f64) -> Vector3 {
-        let mut rng = rand::thread_rng();
+    pub fn random(rng: &mut impl Rng, min: f64, max: f64) -> Vector3 {
         Vector3::new(
             rng.gen_range(min..max),
             rng.gen_range(min..max),
@@ -47,9 +46,8 @@ impl Vector3 {
         *self / self.length()
     }
 
-    pub fn random_in_unit_disk() -> Vector3 {
+    pub fn random_in_unit_disk(rng: &mut impl Rng) -> Vector3 {
         loop {
-            let mut rng = rand::thread_rng();
             let p = Vector3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
             if p.length_squared() < 1.0 {
                 return p;
@@ -57,22 +55,22 @@ impl Vector3 {
         }
     }
 
-    pub fn random_in_unit_sphere() -> Vector3 {
+    pub fn random_in_unit_sphere(rng: &mut impl Rng) -> Vector3 {
         loop {
-            let p = Vector3::random(-1.0, 1.0);
+            let p = Vector3::random(rng, -1.0, 1.0);
             if p.length_squared() < 1.0 {
                 return p;
             }
         }
     }
 
-    pub fn random_unit_vector() -> Vector3 {
-        let vec = Vector3::random_in_unit_sphere();
+    pub fn random_unit_vector(rng: &mut impl Rng) -> Vector3 {
+        let vec = Vector3::random_in_unit_sphere(rng);
         vec.unit_vector()
     }
 
-    pub fn random_on_hemisphere(normal: Vector3) -> Vector3 {
-        let on_unit_sphere = Vector3::random_unit_vector();
+    pub fn random_on_hemisphere(rng: &mut impl Rng, normal: Vector3) -> Vector3 {
+        let on_unit_sphere = Vector3::random_unit_vector(rng);
         if on_unit_sphere.dot(normal) > 0.0 {
             return on_unit_sphere;
         }
@@ -81,7 +79,7 @@ impl Vector3 {
 
     pub fn near_zero(&self) -> bool {
         let s = 1e-8;
-        self.e.iter().all(|&dimension| dimension < s)
+        self.e.iter().all(|&dimension| dimension.abs() < s)
     }
 
     pub fn reflect(vector: Vector3, normal: Vector3) -> Vector3 {